@@ -0,0 +1,470 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+// Matches HEIGHTMAP_MAGIC / HEIGHTMAP_FORMAT_VERSION in heightmap-engine's
+// main.rs. Duplicated here rather than shared because this is a separate
+// binary crate root; heightmap-diff.rs already does the same thing for the
+// same reason.
+const HEIGHTMAP_MAGIC: &[u8; 4] = b"HGHT";
+const HEIGHTMAP_FORMAT_VERSION: u8 = 3;
+// magic(4)+version(1)+bits_per_sample(1)+width(4)+height(4)+seed(8)+flags(2)+crc32c(4)
+const HEADER_SIZE: usize = 28;
+
+// Matches heightmap-engine's `CompressionMode`; duplicated for the same
+// reason the rest of the header format is duplicated in this file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompressionMode {
+    None,
+    Lz4,
+    Deflate,
+}
+
+impl CompressionMode {
+    fn from_flag(flag: u16) -> Result<Self, String> {
+        match flag {
+            0 => Ok(CompressionMode::None),
+            1 => Ok(CompressionMode::Lz4),
+            2 => Ok(CompressionMode::Deflate),
+            other => Err(format!("unsupported compression flag {}", other)),
+        }
+    }
+}
+
+// Mirrors heightmap-engine's TerrainLayer. Only the discriminants matter
+// here since the cost grid is rebuilt from the raw terrain layer bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TerrainLayer {
+    Water,
+    Land,
+    PineMountain,
+    RockMountain,
+}
+
+// Mirrors heightmap-engine's terrain_base_cost. Kept in lockstep: a path
+// found here is only meaningful if it agrees with what the engine actually
+// baked into --debug-nav-cost-bmp.
+fn terrain_base_cost(layer: TerrainLayer) -> f32 {
+    match layer {
+        TerrainLayer::Water => f32::INFINITY,
+        TerrainLayer::Land => 1.0,
+        TerrainLayer::PineMountain => 4.0,
+        TerrainLayer::RockMountain => 8.0,
+    }
+}
+
+// Mirrors heightmap-diff.rs's classify_terrain_layer thresholds, scaled to
+// whichever bit depth the file was written at.
+fn classify_terrain_layer(height_value: u32, bits_per_sample: u8) -> TerrainLayer {
+    let max_sample_value: u32 = if bits_per_sample == 16 { 65535 } else { 255 };
+    let scale = |threshold_255: u32| threshold_255 * max_sample_value / 255;
+
+    if height_value <= scale(79) {
+        TerrainLayer::Water
+    } else if height_value <= scale(159) {
+        TerrainLayer::Land
+    } else if height_value <= scale(219) {
+        TerrainLayer::PineMountain
+    } else {
+        TerrainLayer::RockMountain
+    }
+}
+
+const SLOPE_COST_SCALE: f32 = 0.05;
+
+struct LoadedHeightmap {
+    width: u32,
+    height: u32,
+    bits_per_sample: u8,
+    samples: Vec<u32>,
+}
+
+fn load_heightmap(path: &str) -> Result<LoadedHeightmap, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    if bytes.len() < HEADER_SIZE {
+        return Err(format!("{}: file too small to contain header", path));
+    }
+
+    if &bytes[0..4] != HEIGHTMAP_MAGIC {
+        return Err(format!("{}: bad magic tag", path));
+    }
+
+    let version = bytes[4];
+    if version != HEIGHTMAP_FORMAT_VERSION {
+        return Err(format!(
+            "{}: unsupported format version {} (expected {})",
+            path, version, HEIGHTMAP_FORMAT_VERSION
+        ));
+    }
+
+    let bits_per_sample = bytes[5];
+    if bits_per_sample != 8 && bits_per_sample != 16 {
+        return Err(format!(
+            "{}: unsupported bits_per_sample {} (expected 8 or 16)",
+            path, bits_per_sample
+        ));
+    }
+
+    let width = u32::from_le_bytes(bytes[6..10].try_into().unwrap());
+    let height = u32::from_le_bytes(bytes[10..14].try_into().unwrap());
+    // bytes 14..22: random_seed, not needed here.
+    let compression_mode = CompressionMode::from_flag(u16::from_le_bytes(bytes[22..24].try_into().unwrap()))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let stored_checksum = u32::from_le_bytes(bytes[24..28].try_into().unwrap());
+
+    let bytes_per_sample = (bits_per_sample / 8) as usize;
+    let expected_data_size = (width as usize) * (height as usize) * bytes_per_sample;
+
+    let sample_payload: &[u8] = if compression_mode == CompressionMode::None {
+        if bytes.len() < HEADER_SIZE + expected_data_size {
+            return Err(format!(
+                "{}: file too small for {}x{} at {}-bit",
+                path, width, height, bits_per_sample
+            ));
+        }
+        &bytes[HEADER_SIZE..HEADER_SIZE + expected_data_size]
+    } else {
+        if bytes.len() < HEADER_SIZE + 4 {
+            return Err(format!("{}: file too small to contain sample payload length prefix", path));
+        }
+        let compressed_len = u32::from_le_bytes(bytes[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap()) as usize;
+        if bytes.len() < HEADER_SIZE + 4 + compressed_len {
+            return Err(format!("{}: file too small for a {}-byte compressed sample payload", path, compressed_len));
+        }
+        &bytes[HEADER_SIZE..HEADER_SIZE + 4 + compressed_len]
+    };
+
+    let computed_checksum = crc32c::crc32c(sample_payload);
+    if computed_checksum != stored_checksum {
+        return Err(format!(
+            "{}: checksum mismatch, file may be corrupted or truncated (expected {:08x}, got {:08x})",
+            path, stored_checksum, computed_checksum
+        ));
+    }
+
+    let data_bytes: Vec<u8> = match compression_mode {
+        CompressionMode::None => sample_payload.to_vec(),
+        CompressionMode::Lz4 => lz4_flex::decompress_size_prepended(&sample_payload[4..])
+            .map_err(|e| format!("{}: LZ4 decompress failed: {}", path, e))?,
+        CompressionMode::Deflate => miniz_oxide::inflate::decompress_to_vec(&sample_payload[4..])
+            .map_err(|e| format!("{}: deflate decompress failed: {:?}", path, e))?,
+    };
+    if data_bytes.len() != expected_data_size {
+        return Err(format!(
+            "{}: decompressed sample size mismatch: expected {}, got {}",
+            path, expected_data_size, data_bytes.len()
+        ));
+    }
+
+    let mut samples = Vec::with_capacity((width * height) as usize);
+    if bits_per_sample == 16 {
+        for chunk in data_bytes.chunks_exact(2) {
+            samples.push(u16::from_le_bytes(chunk.try_into().unwrap()) as u32);
+        }
+    } else {
+        for &sample in &data_bytes {
+            samples.push(sample as u32);
+        }
+    }
+
+    Ok(LoadedHeightmap { width, height, bits_per_sample, samples })
+}
+
+// Rebuilds the cost grid heightmap-engine's compute_navigation_cost_grid
+// produces, from the sample buffer alone. Terrain layer bytes aren't read
+// back off disk; they're reclassified here from height the same way
+// heightmap-diff.rs reclassifies them, so a cell the engine pinned with a
+// LockLayer override gets its height-based class here instead. Slope is
+// measured on the engine's 8-bit view of the heights (the top byte of a
+// 16-bit sample), the scale SLOPE_COST_SCALE is defined against.
+fn compute_navigation_cost_grid(samples: &[u32], width: u32, height: u32, bits_per_sample: u8) -> Vec<f32> {
+    let w = width as i32;
+    let h = height as i32;
+    let to_8bit = |sample: u32| -> f32 {
+        if bits_per_sample == 16 { (sample >> 8) as f32 } else { sample as f32 }
+    };
+
+    let mut cost_grid = Vec::with_capacity((width * height) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let idx = (y * w + x) as usize;
+            let layer = classify_terrain_layer(samples[idx], bits_per_sample);
+
+            let base_cost = terrain_base_cost(layer);
+            if !base_cost.is_finite() {
+                cost_grid.push(base_cost);
+                continue;
+            }
+
+            let here = to_8bit(samples[idx]);
+            let mut max_drop: f32 = 0.0;
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if nx < 0 || ny < 0 || nx >= w || ny >= h {
+                    continue;
+                }
+                let neighbor = to_8bit(samples[(ny * w + nx) as usize]);
+                max_drop = max_drop.max((neighbor - here).abs());
+            }
+
+            cost_grid.push(base_cost + SLOPE_COST_SCALE * max_drop);
+        }
+    }
+
+    cost_grid
+}
+
+// Wraps an f32 cost so it can sit in a `BinaryHeap`, which requires `Ord`.
+// A* pulls the *lowest* f cost first, so this reverses the comparison that
+// `f32::partial_cmp` would otherwise give a max-heap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct HeapCost(f32);
+
+impl Eq for HeapCost {}
+
+impl PartialOrd for HeapCost {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapCost {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so BinaryHeap (a max-heap) pops the smallest cost first.
+        // NaN can't appear in a cost grid built from finite heights, so
+        // falling back to Equal on a partial_cmp miss is unreachable in
+        // practice rather than a real tie-breaking policy.
+        other.0.partial_cmp(&self.0).unwrap_or(Ordering::Equal)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HeapEntry {
+    f_cost: HeapCost,
+    cell: (u32, u32),
+}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.f_cost.cmp(&other.f_cost)
+    }
+}
+
+const SQRT2_MINUS_1: f32 = std::f32::consts::SQRT_2 - 1.0;
+
+// Octile/diagonal-distance heuristic, scaled by the cheapest possible
+// terrain cost so it never overestimates the true remaining cost (the
+// admissibility A* needs to guarantee an optimal path).
+fn octile_heuristic(a: (u32, u32), b: (u32, u32), min_terrain_cost: f32) -> f32 {
+    let dx = (a.0 as f32 - b.0 as f32).abs();
+    let dy = (a.1 as f32 - b.1 as f32).abs();
+    (dx.max(dy) + SQRT2_MINUS_1 * dx.min(dy)) * min_terrain_cost
+}
+
+const NEIGHBOR_OFFSETS: [(i32, i32); 8] = [
+    (-1, -1), (0, -1), (1, -1),
+    (-1, 0),           (1, 0),
+    (-1, 1),  (0, 1),  (1, 1),
+];
+
+// A* search over the navigation cost grid using a binary-heap min-priority
+// queue keyed by f cost. Diagonal moves cost `sqrt(2)` times the average of
+// the two cells' costs, so a diagonal step through rough terrain is priced
+// proportionally more than an orthogonal one.
+fn astar(
+    grid: &[f32],
+    width: u32,
+    height: u32,
+    start: (u32, u32),
+    goal: (u32, u32),
+) -> Option<(Vec<(u32, u32)>, f32)> {
+    let w = width as usize;
+    let cell_count = (width * height) as usize;
+    let index = |cell: (u32, u32)| cell.1 as usize * w + cell.0 as usize;
+
+    if grid[index(start)].is_infinite() || grid[index(goal)].is_infinite() {
+        return None;
+    }
+
+    let min_terrain_cost = grid
+        .iter()
+        .copied()
+        .filter(|c| c.is_finite() && *c > 0.0)
+        .fold(f32::INFINITY, f32::min);
+
+    let mut g_cost = vec![f32::INFINITY; cell_count];
+    let mut came_from: Vec<Option<(u32, u32)>> = vec![None; cell_count];
+    let mut open_set = BinaryHeap::new();
+
+    g_cost[index(start)] = 0.0;
+    open_set.push(HeapEntry {
+        f_cost: HeapCost(octile_heuristic(start, goal, min_terrain_cost)),
+        cell: start,
+    });
+
+    while let Some(HeapEntry { cell: current, .. }) = open_set.pop() {
+        if current == goal {
+            let mut path = vec![current];
+            let mut cursor = current;
+            while let Some(previous) = came_from[index(cursor)] {
+                path.push(previous);
+                cursor = previous;
+            }
+            path.reverse();
+            return Some((path, g_cost[index(goal)]));
+        }
+
+        let current_g = g_cost[index(current)];
+
+        for (dx, dy) in NEIGHBOR_OFFSETS {
+            let nx = current.0 as i32 + dx;
+            let ny = current.1 as i32 + dy;
+            if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                continue;
+            }
+            let neighbor = (nx as u32, ny as u32);
+            let neighbor_cost = grid[index(neighbor)];
+            if neighbor_cost.is_infinite() {
+                continue;
+            }
+
+            let step_distance = if dx != 0 && dy != 0 { std::f32::consts::SQRT_2 } else { 1.0 };
+            let step_cost = step_distance * (grid[index(current)] + neighbor_cost) * 0.5;
+            let tentative_g = current_g + step_cost;
+
+            if tentative_g < g_cost[index(neighbor)] {
+                g_cost[index(neighbor)] = tentative_g;
+                came_from[index(neighbor)] = Some(current);
+                open_set.push(HeapEntry {
+                    f_cost: HeapCost(tentative_g + octile_heuristic(neighbor, goal, min_terrain_cost)),
+                    cell: neighbor,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+fn write_grayscale_bmp(output_path: &Path, width: u32, height: u32, pixel_bytes: &[u8]) {
+    use std::io::Write;
+
+    let file_size: u32 = 14 + 40 + 256 * 4 + (width * height);
+    let mut file = fs::File::create(output_path).expect("Failed to create BMP file");
+
+    file.write_all(b"BM").unwrap();
+    file.write_all(&file_size.to_le_bytes()).unwrap();
+    file.write_all(&[0u8; 4]).unwrap();
+
+    let pixel_data_offset: u32 = 14 + 40 + 256 * 4;
+    file.write_all(&pixel_data_offset.to_le_bytes()).unwrap();
+
+    file.write_all(&40u32.to_le_bytes()).unwrap();
+    file.write_all(&width.to_le_bytes()).unwrap();
+    file.write_all(&height.to_le_bytes()).unwrap();
+    file.write_all(&1u16.to_le_bytes()).unwrap();
+    file.write_all(&8u16.to_le_bytes()).unwrap();
+    file.write_all(&0u32.to_le_bytes()).unwrap();
+    file.write_all(&(width * height).to_le_bytes()).unwrap();
+    file.write_all(&[0u8; 16]).unwrap();
+
+    for i in 0..256u32 {
+        let value = i as u8;
+        file.write_all(&[value, value, value, 0]).unwrap();
+    }
+
+    for row in (0..height).rev() {
+        let start = (row * width) as usize;
+        let end = start + width as usize;
+        file.write_all(&pixel_bytes[start..end]).unwrap();
+    }
+}
+
+fn print_usage_and_exit() -> ! {
+    eprintln!(
+        "Usage: heightmap-astar <heightmap> <start_x> <start_y> <goal_x> <goal_y> [--debug-path-bmp <path>]"
+    );
+    process::exit(1);
+}
+
+fn main() {
+    let arguments: Vec<String> = env::args().collect();
+
+    if arguments.len() < 6 {
+        print_usage_and_exit();
+    }
+
+    let path = &arguments[1];
+    let parse_coord = |s: &str| -> u32 {
+        s.parse().unwrap_or_else(|_| {
+            eprintln!("[heightmap-astar] Invalid coordinate: {}", s);
+            process::exit(1);
+        })
+    };
+
+    let start = (parse_coord(&arguments[2]), parse_coord(&arguments[3]));
+    let goal = (parse_coord(&arguments[4]), parse_coord(&arguments[5]));
+
+    let mut debug_path_bmp_path: Option<String> = None;
+    let mut argument_index = 6;
+    while argument_index < arguments.len() {
+        match arguments[argument_index].as_str() {
+            "--debug-path-bmp" => {
+                debug_path_bmp_path = arguments.get(argument_index + 1).cloned();
+                argument_index += 2;
+            }
+            _ => {
+                argument_index += 1;
+            }
+        }
+    }
+
+    let heightmap = load_heightmap(path).unwrap_or_else(|e| {
+        eprintln!("[heightmap-astar] {}", e);
+        process::exit(1);
+    });
+
+    if start.0 >= heightmap.width || start.1 >= heightmap.height {
+        eprintln!("[heightmap-astar] Start {:?} is outside the {}x{} map", start, heightmap.width, heightmap.height);
+        process::exit(1);
+    }
+    if goal.0 >= heightmap.width || goal.1 >= heightmap.height {
+        eprintln!("[heightmap-astar] Goal {:?} is outside the {}x{} map", goal, heightmap.width, heightmap.height);
+        process::exit(1);
+    }
+
+    let cost_grid = compute_navigation_cost_grid(
+        &heightmap.samples,
+        heightmap.width,
+        heightmap.height,
+        heightmap.bits_per_sample,
+    );
+
+    match astar(&cost_grid, heightmap.width, heightmap.height, start, goal) {
+        Some((path, total_cost)) => {
+            println!("[heightmap-astar] Path found: {} cells, total cost {:.2}", path.len(), total_cost);
+
+            if let Some(bmp_path) = debug_path_bmp_path {
+                let cell_count = (heightmap.width * heightmap.height) as usize;
+                let mut debug_bytes = vec![64u8; cell_count];
+                for &(x, y) in &path {
+                    debug_bytes[(y * heightmap.width + x) as usize] = 255;
+                }
+                write_grayscale_bmp(Path::new(&bmp_path), heightmap.width, heightmap.height, &debug_bytes);
+            }
+        }
+        None => {
+            eprintln!("[heightmap-astar] No path exists between {:?} and {:?}", start, goal);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+// Matches HEIGHTMAP_MAGIC / HEIGHTMAP_FORMAT_VERSION in heightmap-engine's
+// main.rs. Duplicated here rather than shared because this is a separate
+// binary crate root; WeatherAnalyses/src/heightmap.rs already does the
+// same thing for the same reason.
+const HEIGHTMAP_MAGIC: &[u8; 4] = b"HGHT";
+const HEIGHTMAP_FORMAT_VERSION: u8 = 3;
+// magic(4)+version(1)+bits_per_sample(1)+width(4)+height(4)+seed(8)+flags(2)+crc32c(4)
+const HEADER_SIZE: usize = 28;
+
+// Matches heightmap-engine's `CompressionMode`; duplicated for the same
+// reason the rest of the header format is duplicated in this file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompressionMode {
+    None,
+    Lz4,
+    Deflate,
+}
+
+impl CompressionMode {
+    fn from_flag(flag: u16) -> Result<Self, String> {
+        match flag {
+            0 => Ok(CompressionMode::None),
+            1 => Ok(CompressionMode::Lz4),
+            2 => Ok(CompressionMode::Deflate),
+            other => Err(format!("unsupported compression flag {}", other)),
+        }
+    }
+}
+
+struct LoadedHeightmap {
+    width: u32,
+    height: u32,
+    bits_per_sample: u8,
+    samples: Vec<i32>,
+}
+
+fn load_heightmap(path: &str) -> Result<LoadedHeightmap, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    if bytes.len() < HEADER_SIZE {
+        return Err(format!("{}: file too small to contain header", path));
+    }
+
+    if &bytes[0..4] != HEIGHTMAP_MAGIC {
+        return Err(format!("{}: bad magic tag", path));
+    }
+
+    let version = bytes[4];
+    if version != HEIGHTMAP_FORMAT_VERSION {
+        return Err(format!(
+            "{}: unsupported format version {} (expected {})",
+            path, version, HEIGHTMAP_FORMAT_VERSION
+        ));
+    }
+
+    let bits_per_sample = bytes[5];
+    if bits_per_sample != 8 && bits_per_sample != 16 {
+        return Err(format!(
+            "{}: unsupported bits_per_sample {} (expected 8 or 16)",
+            path, bits_per_sample
+        ));
+    }
+
+    let width = u32::from_le_bytes(bytes[6..10].try_into().unwrap());
+    let height = u32::from_le_bytes(bytes[10..14].try_into().unwrap());
+    // bytes 14..22: random_seed, not needed here.
+    let compression_mode = CompressionMode::from_flag(u16::from_le_bytes(bytes[22..24].try_into().unwrap()))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let stored_checksum = u32::from_le_bytes(bytes[24..28].try_into().unwrap());
+
+    let bytes_per_sample = (bits_per_sample / 8) as usize;
+    let expected_data_size = (width as usize) * (height as usize) * bytes_per_sample;
+
+    let sample_payload: &[u8] = if compression_mode == CompressionMode::None {
+        if bytes.len() < HEADER_SIZE + expected_data_size {
+            return Err(format!(
+                "{}: file too small for {}x{} at {}-bit",
+                path, width, height, bits_per_sample
+            ));
+        }
+        &bytes[HEADER_SIZE..HEADER_SIZE + expected_data_size]
+    } else {
+        if bytes.len() < HEADER_SIZE + 4 {
+            return Err(format!("{}: file too small to contain sample payload length prefix", path));
+        }
+        let compressed_len = u32::from_le_bytes(bytes[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap()) as usize;
+        if bytes.len() < HEADER_SIZE + 4 + compressed_len {
+            return Err(format!("{}: file too small for a {}-byte compressed sample payload", path, compressed_len));
+        }
+        &bytes[HEADER_SIZE..HEADER_SIZE + 4 + compressed_len]
+    };
+
+    let computed_checksum = crc32c::crc32c(sample_payload);
+    if computed_checksum != stored_checksum {
+        return Err(format!(
+            "{}: checksum mismatch, file may be corrupted or truncated (expected {:08x}, got {:08x})",
+            path, stored_checksum, computed_checksum
+        ));
+    }
+
+    let data_bytes: Vec<u8> = match compression_mode {
+        CompressionMode::None => sample_payload.to_vec(),
+        CompressionMode::Lz4 => lz4_flex::decompress_size_prepended(&sample_payload[4..])
+            .map_err(|e| format!("{}: LZ4 decompress failed: {}", path, e))?,
+        CompressionMode::Deflate => miniz_oxide::inflate::decompress_to_vec(&sample_payload[4..])
+            .map_err(|e| format!("{}: deflate decompress failed: {:?}", path, e))?,
+    };
+    if data_bytes.len() != expected_data_size {
+        return Err(format!(
+            "{}: decompressed sample size mismatch: expected {}, got {}",
+            path, expected_data_size, data_bytes.len()
+        ));
+    }
+
+    let mut samples = Vec::with_capacity((width * height) as usize);
+    if bits_per_sample == 16 {
+        for chunk in data_bytes.chunks_exact(2) {
+            samples.push(u16::from_le_bytes(chunk.try_into().unwrap()) as i32);
+        }
+    } else {
+        for &sample in &data_bytes {
+            samples.push(sample as i32);
+        }
+    }
+
+    Ok(LoadedHeightmap {
+        width,
+        height,
+        bits_per_sample,
+        samples,
+    })
+}
+
+// Mirrors heightmap-engine's classify_terrain_layer thresholds, scaled to
+// whichever bit depth the file was written at. Kept in lockstep with the
+// thresholds in main.rs; a terrain-layer diff is only meaningful if this
+// matches.
+fn classify_terrain_layer(height_value: u32, bits_per_sample: u8) -> u8 {
+    let max_sample_value: u32 = if bits_per_sample == 16 { 65535 } else { 255 };
+    let scale = |threshold_255: u32| threshold_255 * max_sample_value / 255;
+
+    if height_value <= scale(79) {
+        0 // Water
+    } else if height_value <= scale(159) {
+        1 // Land
+    } else if height_value <= scale(219) {
+        2 // PineMountain
+    } else {
+        3 // RockMountain
+    }
+}
+
+fn write_grayscale_bmp(output_path: &Path, width: u32, height: u32, pixel_bytes: &[u8]) {
+    use std::io::Write;
+
+    let file_size: u32 = 14 + 40 + 256 * 4 + (width * height);
+    let mut file = fs::File::create(output_path).expect("Failed to create BMP file");
+
+    file.write_all(b"BM").unwrap();
+    file.write_all(&file_size.to_le_bytes()).unwrap();
+    file.write_all(&[0u8; 4]).unwrap();
+
+    let pixel_data_offset: u32 = 14 + 40 + 256 * 4;
+    file.write_all(&pixel_data_offset.to_le_bytes()).unwrap();
+
+    file.write_all(&40u32.to_le_bytes()).unwrap();
+    file.write_all(&width.to_le_bytes()).unwrap();
+    file.write_all(&height.to_le_bytes()).unwrap();
+    file.write_all(&1u16.to_le_bytes()).unwrap();
+    file.write_all(&8u16.to_le_bytes()).unwrap();
+    file.write_all(&0u32.to_le_bytes()).unwrap();
+    file.write_all(&(width * height).to_le_bytes()).unwrap();
+    file.write_all(&[0u8; 16]).unwrap();
+
+    for i in 0..256u32 {
+        let value = i as u8;
+        file.write_all(&[value, value, value, 0]).unwrap();
+    }
+
+    for row in (0..height).rev() {
+        let start = (row * width) as usize;
+        let end = start + width as usize;
+        file.write_all(&pixel_bytes[start..end]).unwrap();
+    }
+}
+
+fn print_usage_and_exit() -> ! {
+    eprintln!(
+        "Usage: heightmap-diff <a.heightmap> <b.heightmap> [--debug-diff-bmp <path>]"
+    );
+    process::exit(1);
+}
+
+fn main() {
+    let arguments: Vec<String> = env::args().collect();
+
+    if arguments.len() < 3 {
+        print_usage_and_exit();
+    }
+
+    let path_a = &arguments[1];
+    let path_b = &arguments[2];
+
+    let mut debug_diff_bmp_path: Option<String> = None;
+    let mut argument_index = 3;
+    while argument_index < arguments.len() {
+        match arguments[argument_index].as_str() {
+            "--debug-diff-bmp" => {
+                debug_diff_bmp_path = arguments.get(argument_index + 1).cloned();
+                argument_index += 2;
+            }
+            _ => {
+                argument_index += 1;
+            }
+        }
+    }
+
+    let heightmap_a = load_heightmap(path_a).unwrap_or_else(|e| {
+        eprintln!("[heightmap-diff] {}", e);
+        process::exit(1);
+    });
+    let heightmap_b = load_heightmap(path_b).unwrap_or_else(|e| {
+        eprintln!("[heightmap-diff] {}", e);
+        process::exit(1);
+    });
+
+    if heightmap_a.width != heightmap_b.width || heightmap_a.height != heightmap_b.height {
+        eprintln!(
+            "[heightmap-diff] Dimension mismatch: {}x{} vs {}x{}",
+            heightmap_a.width, heightmap_a.height, heightmap_b.width, heightmap_b.height
+        );
+        process::exit(1);
+    }
+
+    if heightmap_a.bits_per_sample != heightmap_b.bits_per_sample {
+        eprintln!(
+            "[heightmap-diff] Bit depth mismatch: {} vs {}",
+            heightmap_a.bits_per_sample, heightmap_b.bits_per_sample
+        );
+        process::exit(1);
+    }
+
+    let width = heightmap_a.width;
+    let height = heightmap_a.height;
+    let bits_per_sample = heightmap_a.bits_per_sample;
+    let cell_count = (width * height) as usize;
+
+    let mut deltas: Vec<u32> = Vec::with_capacity(cell_count);
+    let mut min_delta: u32 = u32::MAX;
+    let mut max_delta: u32 = 0;
+    let mut sum_delta: u64 = 0;
+    let mut sum_squared_delta: f64 = 0.0;
+    let mut changed_layer_count: u32 = 0;
+
+    for i in 0..cell_count {
+        let a = heightmap_a.samples[i];
+        let b = heightmap_b.samples[i];
+        let delta = (a - b).unsigned_abs();
+
+        deltas.push(delta);
+        min_delta = min_delta.min(delta);
+        max_delta = max_delta.max(delta);
+        sum_delta += delta as u64;
+        sum_squared_delta += (delta as f64) * (delta as f64);
+
+        if classify_terrain_layer(a as u32, bits_per_sample)
+            != classify_terrain_layer(b as u32, bits_per_sample)
+        {
+            changed_layer_count += 1;
+        }
+    }
+
+    let mean_delta = sum_delta as f64 / cell_count as f64;
+    let rms_delta = (sum_squared_delta / cell_count as f64).sqrt();
+    let changed_layer_percentage = 100.0 * changed_layer_count as f64 / cell_count as f64;
+
+    println!("[heightmap-diff] Comparing {} vs {}", path_a, path_b);
+    println!("[heightmap-diff] Dimensions: {}x{} ({}-bit)", width, height, bits_per_sample);
+    println!("[heightmap-diff] Min delta:  {}", min_delta);
+    println!("[heightmap-diff] Max delta:  {}", max_delta);
+    println!("[heightmap-diff] Mean delta: {:.4}", mean_delta);
+    println!("[heightmap-diff] RMS delta:  {:.4}", rms_delta);
+    println!(
+        "[heightmap-diff] Terrain layer changes: {} / {} cells ({:.2}%)",
+        changed_layer_count, cell_count, changed_layer_percentage
+    );
+
+    if let Some(path) = debug_diff_bmp_path {
+        // Scale deltas into 0..255 against the observed max, so even a
+        // subtle perturbation is visible; an all-zero diff renders as
+        // solid black rather than dividing by zero.
+        let scale_denominator = max_delta.max(1) as f32;
+        let debug_bytes: Vec<u8> = deltas
+            .iter()
+            .map(|&d| ((d as f32 / scale_denominator) * 255.0).round() as u8)
+            .collect();
+
+        write_grayscale_bmp(Path::new(&path), width, height, &debug_bytes);
+    }
+}
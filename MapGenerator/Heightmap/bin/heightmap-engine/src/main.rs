@@ -1,8 +1,10 @@
 use std::io::Write;
-use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process;
 
+use anyhow::{Context, Result};
+use clap::Parser;
 use serde::Deserialize;
 use rand::Rng;
 use rand::SeedableRng;
@@ -15,7 +17,8 @@ use rand_chacha::ChaCha8Rng;
 // and must remain stable for the tiler to interpret correctly.
 //
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 enum TerrainLayer {
     Water = 0,
     Land = 1,
@@ -30,12 +33,34 @@ enum TerrainLayer {
 // Changing these thresholds will change terrain distribution,
 // but will not break the binary format.
 //
-fn classify_terrain_layer(height_value: u8) -> TerrainLayer {
-    match height_value {
-        0..=79 => TerrainLayer::Water,
-        80..=159 => TerrainLayer::Land,
-        160..=219 => TerrainLayer::PineMountain,
-        _ => TerrainLayer::RockMountain,
+// `height_value` is in the native range of `bits_per_sample` (0..=255 for
+// 8-bit, 0..=65535 for 16-bit); thresholds below are defined against the
+// classic 0..=255 scale and rescaled to whatever range is in play.
+//
+fn classify_terrain_layer(height_value: u32, bits_per_sample: u8) -> TerrainLayer {
+    let max_sample_value: u32 = if bits_per_sample == 16 { 65535 } else { 255 };
+    let scale = |threshold_255: u32| threshold_255 * max_sample_value / 255;
+
+    if height_value <= scale(79) {
+        TerrainLayer::Water
+    } else if height_value <= scale(159) {
+        TerrainLayer::Land
+    } else if height_value <= scale(219) {
+        TerrainLayer::PineMountain
+    } else {
+        TerrainLayer::RockMountain
+    }
+}
+
+//
+// Append one normalized height sample to an output buffer, as 1 byte
+// (8-bit) or 2 little-endian bytes (16-bit).
+//
+fn push_sample(buffer: &mut Vec<u8>, sample_value: u32, bits_per_sample: u8) {
+    if bits_per_sample == 16 {
+        buffer.extend_from_slice(&(sample_value as u16).to_le_bytes());
+    } else {
+        buffer.push(sample_value as u8);
     }
 }
 
@@ -265,10 +290,1048 @@ struct HeightmapJob {
     fault_line_iteration_count: Option<u32>,
     random_seed: u64,
 
+    // Output sample width. 8 (default, one byte per cell) or 16 (two
+    // little-endian bytes per cell, for large maps where 256 discrete
+    // levels cause visible terracing). Can also be set with --bit-depth.
+    bits_per_sample: Option<u8>,
+
+    // Fault-formation tuning. Displacement decays linearly from
+    // fault_displacement_max (early iterations, broad structure) to
+    // fault_displacement_min (late iterations, fine detail). Both default
+    // to the original fixed displacement of 2.0 (no decay) if omitted.
+    fault_displacement_min: Option<f32>,
+    fault_displacement_max: Option<f32>,
+    fault_mode: Option<FaultMode>,
+
+    // FIR erosion smoothing constant in 0.0..1.0, applied as a four-pass
+    // sweep after all fault iterations. None/0.0 disables the pass.
+    fir_smoothing_k: Option<f32>,
+
+    // Number of simulated rain droplets to run after FIR smoothing, each
+    // eroding uphill terrain it crosses and depositing sediment downhill.
+    // None/0 disables the pass; it's the most expensive stage so it's off
+    // by default.
+    erosion_droplet_count: Option<u32>,
+
+    // Designer-authored overrides (plateaus, water channels, locked
+    // mountain passes) applied after normalization, before classification.
+    region_overrides: Option<Vec<RegionOverride>>,
+
+    // Compression applied to the heightmap sample layer on disk. None
+    // (the default) keeps the original uncompressed layout.
+    compression_mode: Option<CompressionMode>,
+
     #[allow(dead_code)]
     requested_at_utc: String,
 }
 
+// Per-layer compression for the output file's sample data, recorded in the
+// header's `flags` field so readers know how to undo it. Mirrors
+// WeatherAnalyses's `weather_map::CompressionMode`, duplicated here for the
+// same reason the header-format constants are duplicated in heightmap-diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CompressionMode {
+    None,
+    Lz4,
+    Deflate,
+}
+
+impl CompressionMode {
+    fn flag(self) -> u16 {
+        match self {
+            CompressionMode::None => 0,
+            CompressionMode::Lz4 => 1,
+            CompressionMode::Deflate => 2,
+        }
+    }
+}
+
+// Shape of a single fault-formation iteration.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum FaultMode {
+    // Hard step on either side of a random line (the original behavior).
+    Line,
+    // Cosine-shaped bump around a random center and radius, avoiding the
+    // harsh straight-line step artifacts of line mode.
+    Circular,
+}
+
+//
+// A single override region: designers stamp these onto the job to
+// guarantee a buildable plateau, carve a water channel, or reserve a
+// mountain pass without re-rolling the whole seed.
+//
+#[derive(Debug, Deserialize, Clone, Copy)]
+struct RegionOverride {
+    shape: RegionShape,
+    effect: RegionEffect,
+
+    // Cells beyond the shape's edge but within this many cells still get
+    // a partial (linearly-fading) effect, so overrides don't create hard
+    // seams against the fault-generated terrain. 0 means a hard edge.
+    #[serde(default)]
+    falloff_radius: f32,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+enum RegionShape {
+    Rect {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Circle {
+        center_x: f32,
+        center_y: f32,
+        radius: f32,
+    },
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+enum RegionEffect {
+    // Clamp the cell's normalized height (0.0..1.0) to a fixed value.
+    SetHeight { value: f32 },
+    // Raise or lower the cell's normalized height by a fixed delta,
+    // clamped back into 0.0..1.0.
+    OffsetHeight { delta: f32 },
+    // Pin the cell to a specific terrain layer regardless of height, by
+    // nudging its normalized height toward that layer's band midpoint and
+    // forcing classification once the cell is fully inside the shape.
+    LockLayer { layer: TerrainLayer },
+}
+
+// Distance (in cells) from `x, y` to the outside of `shape`. Zero or
+// negative means the cell is inside the shape.
+fn distance_outside_shape(shape: &RegionShape, x: u32, y: u32) -> f32 {
+    match *shape {
+        RegionShape::Rect {
+            x: rx,
+            y: ry,
+            width,
+            height,
+        } => {
+            let cell_x = x as f32 + 0.5;
+            let cell_y = y as f32 + 0.5;
+
+            // How far outside the rectangle's bounds the cell is along
+            // each axis; 0 if the cell falls within that axis's span.
+            let dx = (rx as f32 - cell_x).max(cell_x - (rx + width) as f32).max(0.0);
+            let dy = (ry as f32 - cell_y).max(cell_y - (ry + height) as f32).max(0.0);
+
+            (dx * dx + dy * dy).sqrt()
+        }
+        RegionShape::Circle {
+            center_x,
+            center_y,
+            radius,
+        } => {
+            let cell_x = x as f32 + 0.5;
+            let cell_y = y as f32 + 0.5;
+            let dist = ((cell_x - center_x).powi(2) + (cell_y - center_y).powi(2)).sqrt();
+            dist - radius
+        }
+    }
+}
+
+// Normalized height band midpoints matching classify_terrain_layer's
+// thresholds (79/159/219 out of 255), used so LockLayer nudges heights
+// toward a value that will actually classify into the requested layer.
+fn layer_band_midpoint(layer: TerrainLayer) -> f32 {
+    match layer {
+        TerrainLayer::Water => 40.0 / 255.0,
+        TerrainLayer::Land => 120.0 / 255.0,
+        TerrainLayer::PineMountain => 190.0 / 255.0,
+        TerrainLayer::RockMountain => 238.0 / 255.0,
+    }
+}
+
+//
+// Apply every region override to the normalized (0.0..1.0) height field in
+// place, and record which cells are fully inside a LockLayer region so
+// classification can skip height-based thresholds for them entirely.
+//
+fn apply_region_overrides(
+    normalized_heights: &mut [f32],
+    locked_layers: &mut [Option<TerrainLayer>],
+    width: u32,
+    height: u32,
+    overrides: &[RegionOverride],
+) {
+    for region in overrides {
+        for y in 0..height {
+            for x in 0..width {
+                let distance = distance_outside_shape(&region.shape, x, y);
+
+                let weight = if distance <= 0.0 {
+                    1.0
+                } else if region.falloff_radius > 0.0 {
+                    (1.0 - distance / region.falloff_radius).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+
+                if weight <= 0.0 {
+                    continue;
+                }
+
+                let idx = (y * width + x) as usize;
+
+                match region.effect {
+                    RegionEffect::SetHeight { value } => {
+                        normalized_heights[idx] =
+                            normalized_heights[idx] * (1.0 - weight) + value * weight;
+                    }
+                    RegionEffect::OffsetHeight { delta } => {
+                        normalized_heights[idx] =
+                            (normalized_heights[idx] + delta * weight).clamp(0.0, 1.0);
+                    }
+                    RegionEffect::LockLayer { layer } => {
+                        let target = layer_band_midpoint(layer);
+                        normalized_heights[idx] =
+                            normalized_heights[idx] * (1.0 - weight) + target * weight;
+                        if distance <= 0.0 {
+                            locked_layers[idx] = Some(layer);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 4-byte magic tag identifying this engine's output format.
+const HEIGHTMAP_MAGIC: &[u8; 4] = b"HGHT";
+// Header format version. Bumped only when the header layout itself
+// changes; bits_per_sample already carries the 8-bit/16-bit distinction.
+// v2 added a trailing CRC32C of the sample buffer, for bit-rot and
+// truncated-write detection on load. v3 inserted a `flags` field ahead of
+// the checksum, recording the compression mode applied to the sample
+// buffer (0 = none, 1 = lz4, 2 = deflate).
+const HEIGHTMAP_FORMAT_VERSION: u8 = 3;
+
+//
+// Apply one line-mode fault iteration.
+//
+// Picks two random points to define a line, then for every cell applies
+// `displacement` to one side and subtracts it from the other, using the
+// signed 2D cross product to determine which side a cell is on. Repeating
+// many times with many random lines builds up ridges.
+//
+fn apply_line_fault(
+    height_accumulator_values: &mut [i32],
+    deterministic_rng: &mut ChaCha8Rng,
+    map_width_in_cells: u32,
+    map_height_in_cells: u32,
+    displacement: f32,
+    fault_iteration_index: u32,
+) {
+    let line_point_one_x: f32 = deterministic_rng.gen_range(0.0..map_width_in_cells as f32);
+    let line_point_one_y: f32 = deterministic_rng.gen_range(0.0..map_height_in_cells as f32);
+
+    let line_point_two_x: f32 = deterministic_rng.gen_range(0.0..map_width_in_cells as f32);
+    let line_point_two_y: f32 = deterministic_rng.gen_range(0.0..map_height_in_cells as f32);
+
+    let line_direction_x: f32 = line_point_two_x - line_point_one_x;
+    let line_direction_y: f32 = line_point_two_y - line_point_one_y;
+
+    // If both points are almost identical, skip this iteration. This
+    // avoids divide-by-zero-like edge cases in our geometry.
+    let line_length_squared: f32 =
+        (line_direction_x * line_direction_x) + (line_direction_y * line_direction_y);
+
+    if line_length_squared < 0.0001 {
+        println!(
+            "[heightmap-engine] Skipping degenerate fault line at iteration {}",
+            fault_iteration_index
+        );
+        return;
+    }
+
+    let displacement_amount: i32 = displacement.round() as i32;
+
+    for row_index in 0..map_height_in_cells {
+        for column_index in 0..map_width_in_cells {
+            let cell_center_x: f32 = column_index as f32 + 0.5;
+            let cell_center_y: f32 = row_index as f32 + 0.5;
+
+            let vector_from_line_to_cell_x: f32 = cell_center_x - line_point_one_x;
+            let vector_from_line_to_cell_y: f32 = cell_center_y - line_point_one_y;
+
+            let signed_cross_product_value: f32 = (vector_from_line_to_cell_x
+                * line_direction_y)
+                - (vector_from_line_to_cell_y * line_direction_x);
+
+            let accumulator_index: usize =
+                (row_index * map_width_in_cells + column_index) as usize;
+
+            if signed_cross_product_value >= 0.0 {
+                height_accumulator_values[accumulator_index] += displacement_amount;
+            } else {
+                height_accumulator_values[accumulator_index] -= displacement_amount;
+            }
+        }
+    }
+}
+
+//
+// Apply one circular-mode fault iteration.
+//
+// Picks a random center and radius, then raises (or lowers, at random) a
+// cosine-shaped bump centered there: full `displacement` at the center,
+// tapering smoothly to zero at the radius. Unlike line mode this leaves no
+// sharp step, so it is better suited for rounded hills than sharp ridges.
+//
+fn apply_circular_fault(
+    height_accumulator_values: &mut [i32],
+    deterministic_rng: &mut ChaCha8Rng,
+    map_width_in_cells: u32,
+    map_height_in_cells: u32,
+    displacement: f32,
+) {
+    let center_x: f32 = deterministic_rng.gen_range(0.0..map_width_in_cells as f32);
+    let center_y: f32 = deterministic_rng.gen_range(0.0..map_height_in_cells as f32);
+
+    let max_dimension = map_width_in_cells.max(map_height_in_cells) as f32;
+    let radius: f32 = deterministic_rng.gen_range((max_dimension * 0.05)..(max_dimension * 0.5));
+
+    let signed_displacement: f32 = if deterministic_rng.gen_bool(0.5) {
+        displacement
+    } else {
+        -displacement
+    };
+
+    for row_index in 0..map_height_in_cells {
+        for column_index in 0..map_width_in_cells {
+            let cell_center_x: f32 = column_index as f32 + 0.5;
+            let cell_center_y: f32 = row_index as f32 + 0.5;
+
+            let dx = cell_center_x - center_x;
+            let dy = cell_center_y - center_y;
+            let dist = (dx * dx + dy * dy).sqrt();
+
+            if dist >= radius {
+                continue;
+            }
+
+            let bump = signed_displacement
+                * ((std::f32::consts::PI * dist / radius).cos() * 0.5 + 0.5);
+
+            let accumulator_index: usize =
+                (row_index * map_width_in_cells + column_index) as usize;
+            height_accumulator_values[accumulator_index] += bump.round() as i32;
+        }
+    }
+}
+
+//
+// FIR erosion smoothing.
+//
+// Sweeps each row left-to-right then right-to-left, and each column
+// top-to-bottom then bottom-to-top, carrying a fraction `k` of the
+// previously visited neighbor's value into the current cell:
+// `cur = k * prev + (1 - k) * cur`. Four directional passes keep the
+// result symmetric along each axis; a plain single-direction sweep would drag detail in
+// one direction only. `k` close to 1.0 smooths aggressively, `k` close to
+// 0.0 barely touches the surface.
+//
+fn fir_smooth(
+    height_accumulator_values: &mut [i32],
+    map_width_in_cells: u32,
+    map_height_in_cells: u32,
+    k: f32,
+) {
+    let width = map_width_in_cells as usize;
+    let height = map_height_in_cells as usize;
+
+    for row in 0..height {
+        let row_start = row * width;
+
+        let mut prev = height_accumulator_values[row_start] as f32;
+        for column in 1..width {
+            let idx = row_start + column;
+            let cur = height_accumulator_values[idx] as f32;
+            let smoothed = k * prev + (1.0 - k) * cur;
+            height_accumulator_values[idx] = smoothed.round() as i32;
+            prev = smoothed;
+        }
+
+        let mut prev = height_accumulator_values[row_start + width - 1] as f32;
+        for column in (0..width - 1).rev() {
+            let idx = row_start + column;
+            let cur = height_accumulator_values[idx] as f32;
+            let smoothed = k * prev + (1.0 - k) * cur;
+            height_accumulator_values[idx] = smoothed.round() as i32;
+            prev = smoothed;
+        }
+    }
+
+    for column in 0..width {
+        let mut prev = height_accumulator_values[column] as f32;
+        for row in 1..height {
+            let idx = row * width + column;
+            let cur = height_accumulator_values[idx] as f32;
+            let smoothed = k * prev + (1.0 - k) * cur;
+            height_accumulator_values[idx] = smoothed.round() as i32;
+            prev = smoothed;
+        }
+
+        let mut prev = height_accumulator_values[(height - 1) * width + column] as f32;
+        for row in (0..height - 1).rev() {
+            let idx = row * width + column;
+            let cur = height_accumulator_values[idx] as f32;
+            let smoothed = k * prev + (1.0 - k) * cur;
+            height_accumulator_values[idx] = smoothed.round() as i32;
+            prev = smoothed;
+        }
+    }
+}
+
+//
+// Tunable constants for the hydraulic erosion pass below. Named after the
+// terms from Hans Theobald Beyer's droplet erosion method, which this is
+// an implementation of.
+//
+const EROSION_INERTIA: f32 = 0.05;
+const EROSION_CAPACITY_FACTOR: f32 = 4.0;
+const EROSION_MIN_SLOPE: f32 = 0.01;
+const EROSION_DEPOSITION: f32 = 0.3;
+const EROSION_EROSION: f32 = 0.3;
+const EROSION_EVAPORATION: f32 = 0.01;
+const EROSION_GRAVITY: f32 = 4.0;
+const EROSION_INITIAL_WATER: f32 = 1.0;
+const EROSION_INITIAL_SPEED: f32 = 1.0;
+const EROSION_MAX_LIFETIME: u32 = 30;
+const EROSION_MIN_WATER: f32 = 0.001;
+const EROSION_BRUSH_RADIUS: i32 = 2;
+
+//
+// Bilinearly sample height and gradient at a sub-cell droplet position.
+//
+// `x`/`y` must satisfy `0.0 <= x < width - 1` and `0.0 <= y < height - 1`
+// so the four surrounding cells always exist; callers are responsible for
+// stopping a droplet that has wandered outside that range.
+//
+fn height_and_gradient(heights: &[f32], width: usize, x: f32, y: f32) -> (f32, f32, f32) {
+    let cell_x = x as usize;
+    let cell_y = y as usize;
+    let u = x - cell_x as f32;
+    let v = y - cell_y as f32;
+
+    let idx = |cx: usize, cy: usize| cy * width + cx;
+    let h_nw = heights[idx(cell_x, cell_y)];
+    let h_ne = heights[idx(cell_x + 1, cell_y)];
+    let h_sw = heights[idx(cell_x, cell_y + 1)];
+    let h_se = heights[idx(cell_x + 1, cell_y + 1)];
+
+    let gradient_x = (h_ne - h_nw) * (1.0 - v) + (h_se - h_sw) * v;
+    let gradient_y = (h_sw - h_nw) * (1.0 - u) + (h_se - h_ne) * u;
+
+    let interpolated_height = h_nw * (1.0 - u) * (1.0 - v)
+        + h_ne * u * (1.0 - v)
+        + h_sw * (1.0 - u) * v
+        + h_se * u * v;
+
+    (interpolated_height, gradient_x, gradient_y)
+}
+
+fn add_to_cell(heights: &mut [f32], width: usize, height: usize, x: i32, y: i32, amount: f32) {
+    if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+        return;
+    }
+    heights[y as usize * width + x as usize] += amount;
+}
+
+// Spreads deposited sediment bilinearly across the four cells surrounding
+// the droplet's previous position, the same weighting `height_and_gradient`
+// used to sample it.
+fn deposit_at(heights: &mut [f32], width: usize, height: usize, x: f32, y: f32, amount: f32) {
+    let cell_x = x as i32;
+    let cell_y = y as i32;
+    let u = x - cell_x as f32;
+    let v = y - cell_y as f32;
+
+    add_to_cell(heights, width, height, cell_x, cell_y, amount * (1.0 - u) * (1.0 - v));
+    add_to_cell(heights, width, height, cell_x + 1, cell_y, amount * u * (1.0 - v));
+    add_to_cell(heights, width, height, cell_x, cell_y + 1, amount * (1.0 - u) * v);
+    add_to_cell(heights, width, height, cell_x + 1, cell_y + 1, amount * u * v);
+}
+
+// Removes eroded material from a small radius-weighted brush centered on
+// the droplet's previous position, rather than a single cell, so erosion
+// carves a smooth channel instead of single-cell pits.
+fn erode_at(heights: &mut [f32], width: usize, height: usize, x: f32, y: f32, amount: f32, radius: i32) {
+    let center_x = x.round() as i32;
+    let center_y = y.round() as i32;
+
+    let mut weighted_cells = Vec::new();
+    let mut total_weight = 0.0f32;
+
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let nx = center_x + dx;
+            let ny = center_y + dy;
+            if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                continue;
+            }
+
+            let distance = ((dx * dx + dy * dy) as f32).sqrt();
+            if distance > radius as f32 {
+                continue;
+            }
+
+            let weight = radius as f32 - distance;
+            weighted_cells.push((nx, ny, weight));
+            total_weight += weight;
+        }
+    }
+
+    if total_weight <= 0.0 {
+        return;
+    }
+
+    for (nx, ny, weight) in weighted_cells {
+        let idx = ny as usize * width + nx as usize;
+        heights[idx] -= amount * (weight / total_weight);
+    }
+}
+
+// Simulates one droplet's lifetime and returns the total volume (absolute
+// sum of deposit/erode amounts) it moved, for the caller to tally up into
+// a single "total moved volume" figure across all droplets.
+fn simulate_droplet(
+    heights: &mut [f32],
+    width: usize,
+    height: usize,
+    rng: &mut ChaCha8Rng,
+) -> f32 {
+    let mut pos_x = rng.gen_range(0.0..(width - 1) as f32);
+    let mut pos_y = rng.gen_range(0.0..(height - 1) as f32);
+    let mut dir_x = 0.0f32;
+    let mut dir_y = 0.0f32;
+    let mut speed = EROSION_INITIAL_SPEED;
+    let mut water = EROSION_INITIAL_WATER;
+    let mut sediment = 0.0f32;
+    let mut moved_volume = 0.0f32;
+
+    for _ in 0..EROSION_MAX_LIFETIME {
+        let (old_x, old_y) = (pos_x, pos_y);
+        let (old_height, gradient_x, gradient_y) = height_and_gradient(heights, width, pos_x, pos_y);
+
+        dir_x = dir_x * EROSION_INERTIA - gradient_x * (1.0 - EROSION_INERTIA);
+        dir_y = dir_y * EROSION_INERTIA - gradient_y * (1.0 - EROSION_INERTIA);
+
+        let dir_len = (dir_x * dir_x + dir_y * dir_y).sqrt();
+        if dir_len < 1e-5 {
+            // Flat spot: the blended direction degenerated to (near) zero.
+            // Pick a random direction rather than dividing by ~0 and
+            // sending the droplet to NaN-land.
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            dir_x = angle.cos();
+            dir_y = angle.sin();
+        } else {
+            dir_x /= dir_len;
+            dir_y /= dir_len;
+        }
+
+        pos_x += dir_x;
+        pos_y += dir_y;
+
+        if pos_x < 0.0 || pos_x >= (width - 1) as f32 || pos_y < 0.0 || pos_y >= (height - 1) as f32 {
+            break;
+        }
+
+        let (new_height, _, _) = height_and_gradient(heights, width, pos_x, pos_y);
+        let delta_height = new_height - old_height;
+
+        let capacity = (-delta_height).max(EROSION_MIN_SLOPE) * speed * water * EROSION_CAPACITY_FACTOR;
+
+        if sediment > capacity || delta_height > 0.0 {
+            let deposit_amount = ((sediment - capacity) * EROSION_DEPOSITION).max(0.0);
+            sediment -= deposit_amount;
+            deposit_at(heights, width, height, old_x, old_y, deposit_amount);
+            moved_volume += deposit_amount;
+        } else {
+            let erode_amount = ((capacity - sediment) * EROSION_EROSION).min(-delta_height);
+            erode_at(heights, width, height, old_x, old_y, erode_amount, EROSION_BRUSH_RADIUS);
+            sediment += erode_amount;
+            moved_volume += erode_amount;
+        }
+
+        speed = (speed * speed - delta_height * EROSION_GRAVITY).max(0.0).sqrt();
+        water *= 1.0 - EROSION_EVAPORATION;
+
+        if water < EROSION_MIN_WATER {
+            break;
+        }
+    }
+
+    moved_volume
+}
+
+//
+// Hydraulic erosion pass: simulates `droplet_count` independent rain
+// droplets over the height accumulator, each eroding uphill terrain it
+// passes through and depositing sediment downhill, carving drainage-like
+// channels that the fault-line + FIR-smoothing passes alone don't produce.
+//
+// Runs on an f32 copy of the accumulator (droplet deltas are sub-integer)
+// and rounds back into `values` once every droplet has run.
+//
+fn apply_hydraulic_erosion(
+    values: &mut Vec<i32>,
+    width: u32,
+    height: u32,
+    droplet_count: u32,
+    rng: &mut ChaCha8Rng,
+) -> f32 {
+    let width_usize = width as usize;
+    let height_usize = height as usize;
+
+    // Guard clause: droplets need at least a 3x3 grid to interpolate
+    // height and gradient without running off the edge immediately.
+    if droplet_count == 0 || width_usize < 3 || height_usize < 3 {
+        return 0.0;
+    }
+
+    let mut heights: Vec<f32> = values.iter().map(|&v| v as f32).collect();
+    let mut total_moved_volume = 0.0f32;
+
+    for _ in 0..droplet_count {
+        total_moved_volume += simulate_droplet(&mut heights, width_usize, height_usize, rng);
+    }
+
+    for (value, &h) in values.iter_mut().zip(heights.iter()) {
+        *value = h.round() as i32;
+    }
+
+    total_moved_volume
+}
+
+// Number of azimuth directions marched for the horizon map. One byte of
+// output per direction per cell.
+const HORIZON_DIRECTION_COUNT: u32 = 16;
+
+// Z-component magnitude used when building surface normals; larger values
+// flatten the normal map (subtler shading), smaller values exaggerate it.
+const NORMAL_STRENGTH: f32 = 2.0;
+
+//
+// Compute a per-cell surface normal map from the normalized (0..255)
+// heightmap, using central differences.
+//
+// Returns a buffer of 3 bytes per cell (x, y, z, in that order); BMP
+// writers that want BGR ordering are responsible for swapping channels
+// themselves.
+//
+fn compute_normal_map(heights: &[u8], width: u32, height: u32, normal_strength: f32) -> Vec<u8> {
+    let w = width as i32;
+    let h = height as i32;
+    let sample = |x: i32, y: i32| -> f32 {
+        let cx = x.clamp(0, w - 1);
+        let cy = y.clamp(0, h - 1);
+        heights[(cy * w + cx) as usize] as f32
+    };
+
+    let mut normal_bytes = Vec::with_capacity((width * height * 3) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let dx = sample(x + 1, y) - sample(x - 1, y);
+            let dy = sample(x, y + 1) - sample(x, y - 1);
+
+            let nx = -dx;
+            let ny = -dy;
+            let nz = normal_strength;
+            let len = (nx * nx + ny * ny + nz * nz).sqrt().max(0.0001);
+
+            let encode = |c: f32| -> u8 { ((c * 0.5 + 0.5) * 255.0).round() as u8 };
+            normal_bytes.push(encode(nx / len));
+            normal_bytes.push(encode(ny / len));
+            normal_bytes.push(encode(nz / len));
+        }
+    }
+
+    normal_bytes
+}
+
+//
+// Compute a per-cell directional horizon/occlusion map from the normalized
+// heightmap. For each cell, marches outward along `HORIZON_DIRECTION_COUNT`
+// evenly-spaced azimuth directions, tracking the steepest elevation angle
+// seen, and stores `sin(max_angle)` as a byte (0 = open sky, 255 = blocked
+// by the horizon). Lets the tiler precompute cheap ambient occlusion
+// instead of ray-marching the heightmap at runtime.
+//
+fn compute_horizon_map(heights: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let w = width as i32;
+    let h = height as i32;
+    let max_steps = w.max(h);
+
+    let mut horizon_bytes =
+        Vec::with_capacity((width * height * HORIZON_DIRECTION_COUNT) as usize);
+
+    for y in 0..h {
+        for x in 0..w {
+            let origin_height = heights[(y * w + x) as usize] as f32;
+
+            for direction_index in 0..HORIZON_DIRECTION_COUNT {
+                let azimuth =
+                    (direction_index as f32 / HORIZON_DIRECTION_COUNT as f32) * std::f32::consts::TAU;
+                let step_x = azimuth.cos();
+                let step_y = azimuth.sin();
+
+                let mut max_angle: f32 = 0.0;
+                for step in 1..max_steps {
+                    let sx = x as f32 + step_x * step as f32;
+                    let sy = y as f32 + step_y * step as f32;
+
+                    if sx < 0.0 || sy < 0.0 || sx >= w as f32 || sy >= h as f32 {
+                        break;
+                    }
+
+                    let sample_height = heights[(sy as i32 * w + sx as i32) as usize] as f32;
+                    let distance = step as f32;
+                    let angle = (sample_height - origin_height).atan2(distance);
+                    if angle > max_angle {
+                        max_angle = angle;
+                    }
+                }
+
+                horizon_bytes.push((max_angle.sin().max(0.0) * 255.0).round() as u8);
+            }
+        }
+    }
+
+    horizon_bytes
+}
+
+// Base movement cost for a cell of each terrain class, before the slope
+// penalty is applied. Water is impassable rather than merely expensive, so
+// it is represented as infinity instead of some large finite number —
+// `astar` can then treat "cost == infinity" as "do not path through this
+// cell" without a separate passability check.
+fn terrain_base_cost(layer: TerrainLayer) -> f32 {
+    match layer {
+        TerrainLayer::Water => f32::INFINITY,
+        TerrainLayer::Land => 1.0,
+        TerrainLayer::PineMountain => 4.0,
+        TerrainLayer::RockMountain => 8.0,
+    }
+}
+
+// Scales the per-cell height difference (on the normalized 0..255 scale)
+// into an additive cost penalty, so steep terrain costs more to cross even
+// within a single terrain class.
+const SLOPE_COST_SCALE: f32 = 0.05;
+
+//
+// Derive a per-cell A*-ready movement cost grid from the terrain layer
+// classification plus a slope penalty.
+//
+// Cost is `terrain_base_cost(layer) + SLOPE_COST_SCALE * max_neighbor_drop`,
+// where `max_neighbor_drop` is the steepest absolute height difference
+// between a cell and its four orthogonal neighbors (edge cells only look at
+// the neighbors that exist). Water stays infinite regardless of slope, so
+// it remains impassable rather than merely expensive.
+//
+fn compute_navigation_cost_grid(
+    terrain_layer_bytes: &[u8],
+    heights: &[u8],
+    width: u32,
+    height: u32,
+) -> Vec<f32> {
+    let w = width as i32;
+    let h = height as i32;
+
+    let mut cost_grid = Vec::with_capacity((width * height) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let idx = (y * w + x) as usize;
+            let layer = match terrain_layer_bytes[idx] {
+                0 => TerrainLayer::Water,
+                1 => TerrainLayer::Land,
+                2 => TerrainLayer::PineMountain,
+                _ => TerrainLayer::RockMountain,
+            };
+
+            let base_cost = terrain_base_cost(layer);
+            if !base_cost.is_finite() {
+                cost_grid.push(base_cost);
+                continue;
+            }
+
+            let here = heights[idx] as f32;
+            let mut max_drop: f32 = 0.0;
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if nx < 0 || ny < 0 || nx >= w || ny >= h {
+                    continue;
+                }
+                let neighbor = heights[(ny * w + nx) as usize] as f32;
+                max_drop = max_drop.max((neighbor - here).abs());
+            }
+
+            cost_grid.push(base_cost + SLOPE_COST_SCALE * max_drop);
+        }
+    }
+
+    cost_grid
+}
+
+//
+// Write a debug BMP for a normal map buffer (3 bytes per cell, RGB order).
+// BMP pixel data is BGR, so channels are swapped on the way out.
+//
+fn write_normal_bmp(output_path: &Path, width: u32, height: u32, normal_bytes: &[u8]) {
+    use std::io::Write;
+
+    let bytes_per_pixel: u32 = 3;
+    let image_size: u32 = width * height * bytes_per_pixel;
+    let file_size: u32 = 14 + 40 + image_size;
+
+    let mut file = fs::File::create(output_path).expect("Failed to create BMP file");
+
+    file.write_all(b"BM").unwrap();
+    file.write_all(&file_size.to_le_bytes()).unwrap();
+    file.write_all(&[0u8; 4]).unwrap();
+
+    let pixel_data_offset: u32 = 14 + 40;
+    file.write_all(&pixel_data_offset.to_le_bytes()).unwrap();
+
+    file.write_all(&40u32.to_le_bytes()).unwrap();
+    file.write_all(&width.to_le_bytes()).unwrap();
+    file.write_all(&height.to_le_bytes()).unwrap();
+    file.write_all(&1u16.to_le_bytes()).unwrap();
+    file.write_all(&24u16.to_le_bytes()).unwrap();
+    file.write_all(&0u32.to_le_bytes()).unwrap();
+    file.write_all(&image_size.to_le_bytes()).unwrap();
+    file.write_all(&[0u8; 16]).unwrap();
+
+    for row in (0..height).rev() {
+        for col in 0..width {
+            let idx = ((row * width + col) * 3) as usize;
+            let (r, g, b) = (normal_bytes[idx], normal_bytes[idx + 1], normal_bytes[idx + 2]);
+            file.write_all(&[b, g, r]).unwrap();
+        }
+    }
+}
+
+//
+// CRC-32 (the IEEE/zlib/PNG polynomial), computed bit-by-bit. PNG chunks
+// and nothing else in this engine need it, so a table-free implementation
+// is simpler than keeping a 256-entry lookup table in sync.
+//
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFFFFFF;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xEDB88320;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc ^ 0xFFFFFFFF
+}
+
+//
+// Adler-32, as used by zlib to checksum the uncompressed stream.
+//
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for &byte in data {
+        a = (a + byte as u32) % MOD_ADLER;
+        b = (b + a) % MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+//
+// Wrap raw bytes in a minimal zlib stream using uncompressed ("stored")
+// DEFLATE blocks. No real compression happens; this exists purely so the
+// output is a spec-valid PNG IDAT payload without pulling in a DEFLATE
+// dependency.
+//
+fn zlib_stored_wrap(data: &[u8]) -> Vec<u8> {
+    const MAX_STORED_BLOCK_SIZE: usize = 65535;
+
+    let mut stream = Vec::with_capacity(data.len() + data.len() / MAX_STORED_BLOCK_SIZE + 16);
+
+    // zlib header: CMF=0x78 (deflate, 32k window), FLG=0x01 (no dict,
+    // chosen so (CMF*256 + FLG) is a multiple of 31 as the spec requires).
+    stream.push(0x78);
+    stream.push(0x01);
+
+    if data.is_empty() {
+        stream.push(0x01); // final, stored, zero-length block
+        stream.extend_from_slice(&0u16.to_le_bytes());
+        stream.extend_from_slice(&0xFFFFu16.to_le_bytes());
+    } else {
+        let mut offset = 0;
+        while offset < data.len() {
+            let remaining = data.len() - offset;
+            let block_len = remaining.min(MAX_STORED_BLOCK_SIZE);
+            let is_final = offset + block_len == data.len();
+
+            stream.push(if is_final { 0x01 } else { 0x00 });
+            stream.extend_from_slice(&(block_len as u16).to_le_bytes());
+            stream.extend_from_slice(&(!(block_len as u16)).to_le_bytes());
+            stream.extend_from_slice(&data[offset..offset + block_len]);
+
+            offset += block_len;
+        }
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+//
+// Write a single PNG chunk: 4-byte big-endian length, 4-byte type, the
+// data itself, then a 4-byte big-endian CRC-32 over type + data.
+//
+fn write_png_chunk(file: &mut fs::File, chunk_type: &[u8; 4], data: &[u8]) {
+    file.write_all(&(data.len() as u32).to_be_bytes()).unwrap();
+    file.write_all(chunk_type).unwrap();
+    file.write_all(data).unwrap();
+
+    let mut crc_input = Vec::with_capacity(4 + data.len());
+    crc_input.extend_from_slice(chunk_type);
+    crc_input.extend_from_slice(data);
+    file.write_all(&crc32(&crc_input).to_be_bytes()).unwrap();
+}
+
+// The 8-byte sequence every PNG file starts with.
+const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+
+//
+// Write the signature, IHDR, and IEND common to every PNG this engine
+// emits, with a single IDAT chunk in between holding `scanlines` wrapped
+// in a stored-block zlib stream.
+//
+fn write_png(
+    output_path: &Path,
+    width: u32,
+    height: u32,
+    color_type: u8,
+    scanlines: &[u8],
+) {
+    let mut file = fs::File::create(output_path).expect("Failed to create PNG file");
+
+    file.write_all(&PNG_SIGNATURE).unwrap();
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.push(8); // bit depth
+    ihdr.push(color_type);
+    ihdr.push(0); // compression method (deflate, the only defined value)
+    ihdr.push(0); // filter method (the only defined value)
+    ihdr.push(0); // interlace method (none)
+    write_png_chunk(&mut file, b"IHDR", &ihdr);
+
+    let idat_payload = zlib_stored_wrap(scanlines);
+    write_png_chunk(&mut file, b"IDAT", &idat_payload);
+
+    write_png_chunk(&mut file, b"IEND", &[]);
+}
+
+//
+// Write an 8-bit grayscale PNG (color type 0), the PNG analogue of
+// `write_grayscale_bmp`.
+//
+fn write_grayscale_png(output_path: &Path, width: u32, height: u32, pixel_bytes: &[u8]) {
+    // Every scanline is prefixed with a filter-type byte; 0 (None) keeps
+    // this symmetric with the unfiltered BMP writer above.
+    let mut scanlines = Vec::with_capacity(pixel_bytes.len() + height as usize);
+    for row in pixel_bytes.chunks_exact(width as usize) {
+        scanlines.push(0u8);
+        scanlines.extend_from_slice(row);
+    }
+
+    write_png(output_path, width, height, 0, &scanlines);
+}
+
+//
+// Write a 24-bit RGB PNG (color type 2) representing terrain layers, the
+// PNG analogue of `write_layer_bmp`. Unlike BMP, PNG pixel data is RGB
+// (not BGR) and top-down (not bottom-up), so the color table and row
+// order both differ slightly from `write_layer_bmp`.
+//
+fn write_layer_png(output_path: &Path, width: u32, height: u32, layer_bytes: &[u8]) {
+    let mut scanlines = Vec::with_capacity(layer_bytes.len() * 3 + height as usize);
+
+    for row in layer_bytes.chunks_exact(width as usize) {
+        scanlines.push(0u8);
+        for &layer in row {
+            let color = match layer {
+                0 => [0, 0, 255],     // Water → Blue
+                1 => [0, 255, 0],     // Land → Green
+                2 => [0, 128, 0],     // PineMountain → Dark green
+                _ => [128, 128, 128], // RockMountain → Gray
+            };
+            scanlines.extend_from_slice(&color);
+        }
+    }
+
+    write_png(output_path, width, height, 2, &scanlines);
+}
+
+//
+// Command-line arguments, matching the pattern used by the analysis
+// binary: a derived `Parser` plus a `run(args) -> anyhow::Result<()>`
+// that surfaces descriptive errors instead of panicking.
+//
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(long)]
+    job_file: PathBuf,
+
+    #[arg(long)]
+    output_file: PathBuf,
+
+    #[arg(long)]
+    bit_depth: Option<u8>,
+
+    #[arg(long)]
+    debug_height_bmp: Option<PathBuf>,
+
+    #[arg(long)]
+    debug_layer_bmp: Option<PathBuf>,
+
+    #[arg(long)]
+    debug_height_png: Option<PathBuf>,
+
+    #[arg(long)]
+    debug_layer_png: Option<PathBuf>,
+
+    #[arg(long)]
+    emit_normals: bool,
+
+    #[arg(long)]
+    emit_horizon: bool,
+
+    #[arg(long)]
+    debug_normals_bmp: Option<PathBuf>,
+
+    #[arg(long)]
+    debug_horizon_bmp: Option<PathBuf>,
+
+    #[arg(long)]
+    emit_nav_cost: bool,
+
+    #[arg(long)]
+    debug_nav_cost_bmp: Option<PathBuf>,
+}
+
 //
 // Entry point for the heightmap engine.
 //
@@ -279,46 +1342,43 @@ struct HeightmapJob {
 // - Writes a binary output file
 //
 fn main() {
-    let arguments: Vec<String> = env::args().collect();
+    let args = Args::parse();
 
-    if arguments.len() < 5 {
-        eprintln!(
-            "Usage: heightmap-engine --job-file <path> --output-file <path>"
-        );
-        std::process::exit(1);
+    if let Err(e) = run(&args) {
+        eprintln!("Error: {:?}", e);
+        process::exit(1);
     }
+}
 
-    let job_file_path = &arguments[2];
-    let output_file_path = &arguments[4];
-
-    let job_file_contents = fs::read_to_string(job_file_path)
-        .expect("Failed to read job file");
+fn run(args: &Args) -> Result<()> {
+    let job_file_contents = fs::read_to_string(&args.job_file)
+        .with_context(|| format!("Failed to read job file: {:?}", args.job_file))?;
 
     let job: HeightmapJob = serde_json::from_str(&job_file_contents)
-        .expect("Failed to parse job JSON");
+        .with_context(|| format!("Failed to parse job JSON: {:?}", args.job_file))?;
 
-    let mut debug_height_bmp_path: Option<String> = None;
-    let mut debug_layer_bmp_path: Option<String> = None;
+    // A debug image implies we need the underlying map computed even if
+    // the raw buffer wasn't explicitly requested.
+    let emit_normals = args.emit_normals || args.debug_normals_bmp.is_some();
+    let emit_horizon = args.emit_horizon || args.debug_horizon_bmp.is_some();
+    let emit_nav_cost = args.emit_nav_cost || args.debug_nav_cost_bmp.is_some();
 
-    let mut argument_index: usize = 1;
+    //
+    // --bit-depth on the command line wins over the job file; the job
+    // file wins over the 8-bit default.
+    //
+    let bits_per_sample: u8 = args.bit_depth
+        .or(job.bits_per_sample)
+        .unwrap_or(8);
 
-    while argument_index < arguments.len() {
-        match arguments[argument_index].as_str() {
-            "--debug-height-bmp" => {
-                debug_height_bmp_path =
-                    arguments.get(argument_index + 1).cloned();
-                argument_index += 2;
-            }
-            "--debug-layer-bmp" => {
-                debug_layer_bmp_path =
-                    arguments.get(argument_index + 1).cloned();
-                argument_index += 2;
-            }
-            _ => {
-                argument_index += 1;
-            }
-        }
+    if bits_per_sample != 8 && bits_per_sample != 16 {
+        eprintln!(
+            "[heightmap-engine] Unsupported bit depth {}; falling back to 8",
+            bits_per_sample
+        );
     }
+    let bits_per_sample: u8 = if bits_per_sample == 16 { 16 } else { 8 };
+    let max_sample_value: u32 = if bits_per_sample == 16 { 65535 } else { 255 };
 
     println!(
         "[heightmap-engine] Generating {}x{} heightmap for job {}",
@@ -327,14 +1387,29 @@ fn main() {
         job.job_id
     );
 
-    let total_cell_count =
-        job.map_width_in_cells * job.map_height_in_cells;
+    let total_cell_count = job
+        .map_width_in_cells
+        .checked_mul(job.map_height_in_cells)
+        .with_context(|| {
+            format!(
+                "map_width_in_cells ({}) * map_height_in_cells ({}) overflows u32",
+                job.map_width_in_cells, job.map_height_in_cells
+            )
+        })?;
+
+    //
+   // Output buffer for normalized height values, at whatever bit depth
+   // was selected: 1 byte per cell for 8-bit, 2 little-endian bytes per
+   // cell for 16-bit.
+   //
+    let mut heightmap_sample_bytes: Vec<u8> =
+        Vec::with_capacity(total_cell_count as usize * (bits_per_sample as usize / 8));
 
     //
-   // Output buffer for normalized height values.
-   // One byte per cell.
+   // Always-8-bit view of the same heights, used for the debug BMP
+   // writers (which only understand 0..=255 grayscale).
    //
-    let mut heightmap_bytes: Vec<u8> =
+    let mut heightmap_debug_bytes: Vec<u8> =
         Vec::with_capacity(total_cell_count as usize);
 
     //
@@ -375,93 +1450,79 @@ fn main() {
         ChaCha8Rng::seed_from_u64(job.random_seed);
 
     //
-    // The displacement amount controls ridge strength.
-    //
-    // A larger number creates taller mountains faster.
-    // We will start simple and tune later.
+    // The displacement amount controls ridge strength, and decays
+    // linearly across iterations: early faults build broad structure,
+    // later faults add fine detail. A flat 2.0/2.0 reproduces the
+    // original constant-displacement behavior.
     //
-    let displacement_amount_per_iteration: i32 = 2;
+    let displacement_max: f32 = job.fault_displacement_max.unwrap_or(2.0);
+    let displacement_min: f32 = job.fault_displacement_min.unwrap_or(2.0);
+    let fault_mode: FaultMode = job.fault_mode.unwrap_or(FaultMode::Line);
 
     //
     // Run the fault-line algorithm.
     //
     for fault_iteration_index in 0..fault_line_iteration_count {
-        //
-        // Pick two random points to define a line.
-        //
-        // We pick points in floating point space because
-        // it makes the signed-side test simple and stable.
-        //
-        let line_point_one_x: f32 =
-            deterministic_rng.gen_range(0.0..job.map_width_in_cells as f32);
-        let line_point_one_y: f32 =
-            deterministic_rng.gen_range(0.0..job.map_height_in_cells as f32);
-
-        let line_point_two_x: f32 =
-            deterministic_rng.gen_range(0.0..job.map_width_in_cells as f32);
-        let line_point_two_y: f32 =
-            deterministic_rng.gen_range(0.0..job.map_height_in_cells as f32);
-
-        //
-        // Compute the line direction vector.
-        //
-        let line_direction_x: f32 = line_point_two_x - line_point_one_x;
-        let line_direction_y: f32 = line_point_two_y - line_point_one_y;
-
-        //
-        // If both points are almost identical, skip this iteration.
-        // This avoids divide-by-zero-like edge cases in our geometry.
-        //
-        let line_length_squared: f32 =
-            (line_direction_x * line_direction_x) + (line_direction_y * line_direction_y);
+        let decay_fraction: f32 =
+            fault_iteration_index as f32 / fault_line_iteration_count as f32;
+        let displacement: f32 =
+            displacement_max + (displacement_min - displacement_max) * decay_fraction;
+
+        match fault_mode {
+            FaultMode::Line => apply_line_fault(
+                &mut height_accumulator_values,
+                &mut deterministic_rng,
+                job.map_width_in_cells,
+                job.map_height_in_cells,
+                displacement,
+                fault_iteration_index,
+            ),
+            FaultMode::Circular => apply_circular_fault(
+                &mut height_accumulator_values,
+                &mut deterministic_rng,
+                job.map_width_in_cells,
+                job.map_height_in_cells,
+                displacement,
+            ),
+        }
+    }
 
-        if line_length_squared < 0.0001 {
-            println!(
-                "[heightmap-engine] Skipping degenerate fault line at iteration {}",
-                fault_iteration_index
+    //
+    // Optional FIR erosion smoothing: sweeps each row then each column,
+    // forward and backward, carrying a fraction `k` of the previously
+    // visited neighbor's value forward. This rounds ridges without the
+    // isotropic blur of a box filter.
+    //
+    if let Some(k) = job.fir_smoothing_k {
+        if k > 0.0 {
+            fir_smooth(
+                &mut height_accumulator_values,
+                job.map_width_in_cells,
+                job.map_height_in_cells,
+                k,
             );
-            continue;
         }
+    }
 
-        //
-        // For every cell, determine which side of the line it is on.
-        //
-        // We use the 2D cross product (signed area) to determine side:
-        // cross = (point - line_point_one) x (line_direction)
-        //
-        // - cross > 0 means one side
-        // - cross < 0 means the other side
-        //
-        for row_index in 0..job.map_height_in_cells {
-            for column_index in 0..job.map_width_in_cells {
-                let cell_center_x: f32 = column_index as f32 + 0.5;
-                let cell_center_y: f32 = row_index as f32 + 0.5;
-
-                let vector_from_line_to_cell_x: f32 = cell_center_x - line_point_one_x;
-                let vector_from_line_to_cell_y: f32 = cell_center_y - line_point_one_y;
-
-                let signed_cross_product_value: f32 =
-                    (vector_from_line_to_cell_x * line_direction_y)
-                        - (vector_from_line_to_cell_y * line_direction_x);
-
-                let accumulator_index: usize =
-                    (row_index * job.map_width_in_cells + column_index) as usize;
-
-                //
-                // Apply displacement based on which side we are on.
-                //
-                // This creates a "step" along the fault line.
-                // Repeating many times creates ridges.
-                //
-                if signed_cross_product_value >= 0.0 {
-                    height_accumulator_values[accumulator_index] +=
-                        displacement_amount_per_iteration;
-                } else {
-                    height_accumulator_values[accumulator_index] -=
-                        displacement_amount_per_iteration;
-                }
-            }
-        }
+    //
+    // Optional hydraulic erosion pass. Off by default (the job file does
+    // not have to provide a droplet count), since it's the most expensive
+    // stage.
+    //
+    let erosion_droplet_count = job.erosion_droplet_count.unwrap_or(0);
+    if erosion_droplet_count > 0 {
+        let total_moved_volume = apply_hydraulic_erosion(
+            &mut height_accumulator_values,
+            job.map_width_in_cells,
+            job.map_height_in_cells,
+            erosion_droplet_count,
+            &mut deterministic_rng,
+        );
+
+        println!(
+            "[heightmap-engine] Hydraulic erosion pass complete: {} droplets, total moved volume {:.2}",
+            erosion_droplet_count, total_moved_volume
+        );
     }
 
     // Normalization section, also known as min-max normalization.
@@ -512,122 +1573,227 @@ fn main() {
    //
    // If the range is zero, normalization would cause division by zero.
    //
-    if height_value_range == 0 {
+    let mut normalized_heights: Vec<f32> = if height_value_range == 0 {
         println!(
             "[heightmap-engine] Height range is zero; output will be flat."
         );
-
+        vec![0.5; total_cell_count as usize]
+    } else {
         //
-        // Fill the entire heightmap with a neutral mid-gray value.
+        // Normalize each accumulated height value into the range 0.0..1.0.
         //
-        // 128 is chosen because it sits in the middle of 0..255
-        // and represents a "flat" terrain.
+        // Subtracting the minimum shifts the range to start at zero.
+        // Dividing by the total range scales it to a unit interval.
         //
-        for _ in 0..total_cell_count {
-            heightmap_bytes.push(128);
-        }
-    } else {
+        height_accumulator_values
+            .iter()
+            .map(|&height_value| {
+                (height_value - minimum_height_value) as f32 / height_value_range as f32
+            })
+            .collect()
+    };
+
+    // Cells a LockLayer override fully covers skip height-based
+    // classification entirely, even though their blended height still
+    // feeds the sample/debug buffers like everywhere else.
+    let mut locked_layers: Vec<Option<TerrainLayer>> = vec![None; total_cell_count as usize];
+
+    if let Some(overrides) = &job.region_overrides {
+        apply_region_overrides(
+            &mut normalized_heights,
+            &mut locked_layers,
+            job.map_width_in_cells,
+            job.map_height_in_cells,
+            overrides,
+        );
+    }
 
+    //
+    // Convert each (possibly overridden) normalized height into a sample
+    // at the selected bit depth, an 8-bit debug byte, and a terrain layer
+    // classification.
+    //
+    for (i, &normalized_value_zero_to_one) in normalized_heights.iter().enumerate() {
         //
-        // Normal case: the map has height variation.
-        //
-        // We convert each accumulated height value into a byte.
+        // Scale into the native sample range (0..255 or 0..65535) and
+        // round to avoid truncation bias.
         //
-        for &height_value in height_accumulator_values.iter() {
+        let sample_value: u32 =
+            (normalized_value_zero_to_one * max_sample_value as f32).round() as u32;
 
-            //
-            // Normalize the signed height value into the range 0.0..1.0.
-            //
-            // Subtracting the minimum shifts the range to start at zero.
-            // Dividing by the total range scales it to a unit interval.
-            //
-            let normalized_value_zero_to_one: f32 =
-                (height_value - minimum_height_value) as f32
-                    / height_value_range as f32;
-
-            //
-            // Convert the normalized floating-point value into a byte.
-            //
-            // - Multiply by 255 to scale into byte range
-            // - Round to avoid truncation bias
-            //
-            let normalized_value_zero_to_255: u8 =
-                (normalized_value_zero_to_one * 255.0).round() as u8;
+        push_sample(&mut heightmap_sample_bytes, sample_value, bits_per_sample);
 
-            //
-            // Adding
-            // The height of each cell (0–255)
-            // to the growable array.
-            // This is the primary heightmap output.
-            // And it is storing the normalized value:
-            //
-            heightmap_bytes.push(normalized_value_zero_to_255);
+        //
+        // The debug BMP writers only understand 8-bit grayscale, so
+        // keep an 8-bit view alongside the native-depth samples.
+        //
+        let debug_byte: u8 = if bits_per_sample == 16 {
+            (sample_value >> 8) as u8
+        } else {
+            sample_value as u8
+        };
+        heightmap_debug_bytes.push(debug_byte);
 
-            //
-            // Terrain layer bytes is:  The terrain type of each cell.
-            //                           (water / land / etc.)
-            //
-            // Classify the terrain layer based on height.
-            //
-            // This converts a numeric height into a semantic meaning
-            // such as water, land, pine forest, or rock.
-            //
-            let terrain_layer: TerrainLayer =
-                classify_terrain_layer(normalized_value_zero_to_255);
+        //
+        // Classify the terrain layer based on height, unless a LockLayer
+        // override already pinned this cell to a specific layer.
+        //
+        let terrain_layer: TerrainLayer =
+            locked_layers[i].unwrap_or_else(|| classify_terrain_layer(sample_value, bits_per_sample));
 
-            //
-            // Store the terrain layer as a byte.
-            //
-            // This buffer is parallel to heightmap_bytes:
-            // index N refers to the same cell in both arrays.
-            //
-            terrain_layer_bytes.push(terrain_layer as u8);
-        }
+        terrain_layer_bytes.push(terrain_layer as u8);
     }
 
     // Sanity check.
-    assert_eq!(heightmap_bytes.len(), terrain_layer_bytes.len());
+    assert_eq!(heightmap_debug_bytes.len(), terrain_layer_bytes.len());
+    assert_eq!(
+        heightmap_sample_bytes.len(),
+        total_cell_count as usize * (bits_per_sample as usize / 8)
+    );
+
+    //
+    // Secondary shading products, computed from the 8-bit debug view of
+    // the heightmap. These are optional: most jobs don't need them, and
+    // the horizon map in particular is expensive (one outward march per
+    // direction per cell), so both are gated behind their CLI flags.
+    //
+    let normal_bytes: Option<Vec<u8>> = if emit_normals {
+        Some(compute_normal_map(
+            &heightmap_debug_bytes,
+            job.map_width_in_cells,
+            job.map_height_in_cells,
+            NORMAL_STRENGTH,
+        ))
+    } else {
+        None
+    };
+
+    let horizon_bytes: Option<Vec<u8>> = if emit_horizon {
+        Some(compute_horizon_map(
+            &heightmap_debug_bytes,
+            job.map_width_in_cells,
+            job.map_height_in_cells,
+        ))
+    } else {
+        None
+    };
+
+    // Navigation cost grid, appended to the output file the same way the
+    // normal/horizon buffers are: 4 little-endian bytes per cell (an f32),
+    // row-major. Impassable cells serialize as IEEE-754 infinity rather
+    // than some large sentinel finite cost. `heightmap-astar` does not read
+    // these bytes; it rebuilds the grid from the height samples.
+    let nav_cost_grid: Option<Vec<f32>> = if emit_nav_cost {
+        Some(compute_navigation_cost_grid(
+            &terrain_layer_bytes,
+            &heightmap_debug_bytes,
+            job.map_width_in_cells,
+            job.map_height_in_cells,
+        ))
+    } else {
+        None
+    };
 
-    let output_path = Path::new(output_file_path);
+    let nav_cost_bytes: Option<Vec<u8>> = nav_cost_grid.as_ref().map(|grid| {
+        let mut bytes = Vec::with_capacity(grid.len() * 4);
+        for &cost in grid {
+            bytes.extend_from_slice(&cost.to_le_bytes());
+        }
+        bytes
+    });
 
     //
     // Create the output file.
     //
     // This file will contain:
-    // - A fixed-size header
-    // - The heightmap byte buffer
+    // - A versioned, self-describing header (magic, version, bit depth)
+    // - The legacy width/height/seed fields
+    // - A compression flag and CRC32C for the sample buffer
+    // - The heightmap sample buffer (1 or 2 bytes per cell, optionally
+    //   compressed and length-prefixed)
     // - The terrain layer byte buffer
     //
-    let mut output_file =
-        fs::File::create(output_path)
-            .expect("Failed to create output heightmap file");
+    let mut output_file = fs::File::create(&args.output_file)
+        .with_context(|| format!("Failed to create output heightmap file: {:?}", args.output_file))?;
 
     //
     // Write header fields in little-endian format.
     //
-    // The header allows the tiler to understand the file
-    // without relying on external metadata.
+    // The magic/version/bits_per_sample prefix lets readers (the tiler,
+    // heightmap::Heightmap::load) dispatch on sample width instead of
+    // guessing from file size.
     //
+    output_file
+        .write_all(HEIGHTMAP_MAGIC)
+        .context("Failed to write heightmap magic")?;
+
+    output_file
+        .write_all(&HEIGHTMAP_FORMAT_VERSION.to_le_bytes())
+        .context("Failed to write format version")?;
+
+    output_file
+        .write_all(&bits_per_sample.to_le_bytes())
+        .context("Failed to write bits_per_sample")?;
+
     output_file
         .write_all(&job.map_width_in_cells.to_le_bytes())
-        .expect("Failed to write map width");
+        .context("Failed to write map width")?;
 
     output_file
         .write_all(&job.map_height_in_cells.to_le_bytes())
-        .expect("Failed to write map height");
+        .context("Failed to write map height")?;
 
     output_file
         .write_all(&job.random_seed.to_le_bytes())
-        .expect("Failed to write random seed");
+        .context("Failed to write random seed")?;
+
+    let compression_mode = job.compression_mode.unwrap_or(CompressionMode::None);
+
+    // Large colony maps produce megabytes of mostly-redundant sample data
+    // (flat water, uniform plateaus); compressing it is optional and
+    // recorded in `flags` so Heightmap::load knows whether to undo it.
+    let sample_payload: Vec<u8> = if compression_mode == CompressionMode::None {
+        heightmap_sample_bytes.clone()
+    } else {
+        let compressed = match compression_mode {
+            CompressionMode::None => unreachable!(),
+            CompressionMode::Lz4 => lz4_flex::compress_prepend_size(&heightmap_sample_bytes),
+            CompressionMode::Deflate => miniz_oxide::deflate::compress_to_vec(&heightmap_sample_bytes, 6),
+        };
+        let mut framed = Vec::with_capacity(compressed.len() + 4);
+        framed.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        framed.extend_from_slice(&compressed);
+        framed
+    };
+
+    println!(
+        "[heightmap-engine] Sample layer compression ({:?}): {} bytes -> {} bytes ({:.1}% of original)",
+        compression_mode,
+        heightmap_sample_bytes.len(),
+        sample_payload.len(),
+        100.0 * sample_payload.len() as f64 / heightmap_sample_bytes.len().max(1) as f64
+    );
+
+    output_file
+        .write_all(&compression_mode.flag().to_le_bytes())
+        .context("Failed to write compression flags")?;
+
+    // CRC32C over the on-disk sample payload (post-compression), so
+    // Heightmap::load can detect bit rot or a truncated write before
+    // anything downstream trusts it.
+    let sample_checksum: u32 = crc32c::crc32c(&sample_payload);
+    output_file
+        .write_all(&sample_checksum.to_le_bytes())
+        .context("Failed to write sample checksum")?;
 
     //
-    // Write heightmap data.
-    //
-    // One byte per cell, row-major order.
+    // Write heightmap data: 1 byte per cell at 8-bit, 2 little-endian
+    // bytes per cell at 16-bit, row-major, optionally compressed (see
+    // `compression_mode` above) and length-prefixed when it is.
     //
     output_file
-        .write_all(&heightmap_bytes)
-        .expect("Failed to write heightmap data");
+        .write_all(&sample_payload)
+        .context("Failed to write heightmap data")?;
 
     //
     // Write terrain layer data.
@@ -636,20 +1802,126 @@ fn main() {
     //
     output_file
         .write_all(&terrain_layer_bytes)
-        .expect("Failed to write terrain layer data");
+        .context("Failed to write terrain layer data")?;
+
+    //
+    // Secondary shading buffers, appended after the terrain layer bytes
+    // when requested. Heightmap::load only reads the sample region it
+    // expects and ignores anything past it, so these can be tacked on
+    // without bumping the format version; consumers that want them know
+    // to look because they're the ones that requested the job.
+    //
+    if let Some(normals) = &normal_bytes {
+        output_file
+            .write_all(normals)
+            .context("Failed to write normal map data")?;
+    }
+
+    if let Some(horizon) = &horizon_bytes {
+        output_file
+            .write_all(horizon)
+            .context("Failed to write horizon map data")?;
+    }
 
-    if let Some(path) = debug_height_bmp_path {
+    if let Some(nav_cost) = &nav_cost_bytes {
+        output_file
+            .write_all(nav_cost)
+            .context("Failed to write navigation cost data")?;
+    }
+
+    if let Some(path) = &args.debug_normals_bmp {
+        if let Some(normals) = &normal_bytes {
+            write_normal_bmp(
+                path,
+                job.map_width_in_cells,
+                job.map_height_in_cells,
+                normals,
+            );
+        }
+    }
+
+    if let Some(path) = &args.debug_horizon_bmp {
+        if let Some(horizon) = &horizon_bytes {
+            // Average occlusion across all directions collapses the
+            // per-direction buffer into a single grayscale debug view.
+            let cell_count = (job.map_width_in_cells * job.map_height_in_cells) as usize;
+            let mut average_occlusion = Vec::with_capacity(cell_count);
+            for cell in horizon.chunks_exact(HORIZON_DIRECTION_COUNT as usize) {
+                let sum: u32 = cell.iter().map(|&b| b as u32).sum();
+                average_occlusion.push((sum / HORIZON_DIRECTION_COUNT) as u8);
+            }
+
+            write_grayscale_bmp(
+                path,
+                job.map_width_in_cells,
+                job.map_height_in_cells,
+                &average_occlusion,
+            );
+        }
+    }
+
+    if let Some(path) = &args.debug_nav_cost_bmp {
+        if let Some(grid) = &nav_cost_grid {
+            // Scale against the highest *finite* cost observed so impassable
+            // (infinite) cells don't blow out the scale; they're rendered as
+            // solid white instead, visually distinct from "merely expensive".
+            let max_finite_cost = grid
+                .iter()
+                .copied()
+                .filter(|c| c.is_finite())
+                .fold(0.0f32, f32::max)
+                .max(0.0001);
+
+            let debug_bytes: Vec<u8> = grid
+                .iter()
+                .map(|&cost| {
+                    if cost.is_finite() {
+                        ((cost / max_finite_cost) * 255.0).round() as u8
+                    } else {
+                        255
+                    }
+                })
+                .collect();
+
+            write_grayscale_bmp(
+                path,
+                job.map_width_in_cells,
+                job.map_height_in_cells,
+                &debug_bytes,
+            );
+        }
+    }
+
+    if let Some(path) = &args.debug_height_bmp {
         write_grayscale_bmp(
-            Path::new(&path),
+            path,
             job.map_width_in_cells,
             job.map_height_in_cells,
-            &heightmap_bytes,
+            &heightmap_debug_bytes,
         );
     }
 
-    if let Some(path) = debug_layer_bmp_path {
+    if let Some(path) = &args.debug_layer_bmp {
         write_layer_bmp(
-            Path::new(&path),
+            path,
+            job.map_width_in_cells,
+            job.map_height_in_cells,
+            &terrain_layer_bytes,
+        );
+    }
+
+    if let Some(path) = &args.debug_height_png {
+        write_grayscale_png(
+            path,
+            job.map_width_in_cells,
+            job.map_height_in_cells,
+            &heightmap_debug_bytes,
+        );
+    }
+
+    if let Some(path) = &args.debug_layer_png {
+        write_layer_png(
+            path,
             job.map_width_in_cells,
             job.map_height_in_cells,
             &terrain_layer_bytes,
@@ -657,7 +1929,9 @@ fn main() {
     }
 
     println!(
-        "[heightmap-engine] Output written to {}",
-        output_file_path
+        "[heightmap-engine] Output written to {:?}",
+        args.output_file
     );
+
+    Ok(())
 }
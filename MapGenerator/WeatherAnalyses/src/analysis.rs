@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+
 use crate::heightmap::Heightmap;
+use crate::logger::StageLogger;
 use crate::weather_map::WeatherMap;
 
 // Flow Direction Constants
@@ -12,157 +15,392 @@ use crate::weather_map::WeatherMap;
 // 7 = west
 // 8 = north-west
 
-const DX: [i32; 9] = [0, 0, 1, 1, 1, 0, -1, -1, -1];
-const DY: [i32; 9] = [0, -1, -1, 0, 1, 1, 1, 0, -1];
+pub(crate) const DX: [i32; 9] = [0, 0, 1, 1, 1, 0, -1, -1, -1];
+pub(crate) const DY: [i32; 9] = [0, -1, -1, 0, 1, 1, 1, 0, -1];
 // Distance factors for slope calc (approximate 1.0 vs 1.414 scaled by 1000 or similar? Or just raw height diff?)
 // Spec says "Normalized to engine scale".
 // We will use raw Max Drop for slope and standard D8 for flow.
 
+/// Flow-routing mode for `generate_weather_map_with_mode`.
+///
+/// `D8` keeps the original single steepest-receiver behavior. `Mfd` routes
+/// to every strictly-lower neighbor at once, splitting flow in proportion
+/// to `slope^p` (p=1 is linear slope-proportional, p=0.5 the kinematic-wave
+/// solution), which gives dendritic, dispersive flow on hillslopes instead
+/// of single-cell-wide streaks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlowMode {
+    D8,
+    Mfd { p: f32 },
+}
+
 pub fn generate_weather_map(heightmap: &Heightmap) -> WeatherMap {
+    generate_weather_map_with_mode(heightmap, FlowMode::D8)
+}
+
+pub fn generate_weather_map_with_mode(heightmap: &Heightmap, mode: FlowMode) -> WeatherMap {
+    generate_weather_map_with_mode_and_logger(heightmap, mode, None)
+}
+
+/// Same as `generate_weather_map_with_mode`, but also reports the number of
+/// detected sinks and basins through `logger` (when given) once watershed
+/// labeling completes.
+pub fn generate_weather_map_with_mode_and_logger(
+    heightmap: &Heightmap,
+    mode: FlowMode,
+    logger: Option<&StageLogger>,
+) -> WeatherMap {
     let mut map = WeatherMap::new(heightmap.width, heightmap.height);
 
-    // Pass 1: Slope and Flow Direction
+    // Pre-pass: priority-flood fill so Pass 1 only ever sees real sinks at
+    // the map border. `filled` carries the spill elevation used for routing;
+    // the original heightmap is kept around for rendering/erosion.
+    let fill_span = logger.map(|l| l.start_span("fill_depressions"));
+    let filled = crate::depression::fill_depressions(heightmap);
+    drop(fill_span);
+
+    // Pass 1: Slope and Flow Direction (computed on the filled surface)
+    let flow_span = logger.map(|l| l.start_span("slope_and_flow"));
     for y in 0..heightmap.height {
         for x in 0..heightmap.width {
-            let (slope, flow) = calc_slope_and_flow(heightmap, x, y);
+            let (slope, flow) = calc_slope_and_flow_filled(heightmap, &filled, x, y);
             let idx = (y * heightmap.width + x) as usize;
             map.slope[idx] = slope;
             map.flow[idx] = flow;
         }
     }
 
-    // Pass 2: Basin IDs
-    // Identify sinks and trace basins
-    identify_basins(&mut map, heightmap.width, heightmap.height);
+    if let FlowMode::Mfd { p } = mode {
+        compute_mfd(&mut map, &filled, heightmap.width, heightmap.height, p);
+    }
+    drop(flow_span);
+
+    // Pass 2: proper basin/lake routing (replaces the flat priority-flood
+    // fill with a real spill-point-driven lake surface and reoriented
+    // endorheic-basin outlets). Runs before basin labeling and flow
+    // accumulation below since it rewrites map.flow for every non-root
+    // basin's interior; either of those reading map.flow before this pass
+    // would describe a flow field that no longer exists once it runs.
+    let lake_span = logger.map(|l| l.start_span("route_lakes"));
+    crate::lake::route_lakes(heightmap, &mut map);
+    drop(lake_span);
+
+    // Pass 3: Basin IDs
+    // Identify sinks and trace basins, over the final (post-lake-routing) flow field.
+    let basin_span = logger.map(|l| l.start_span("identify_basins"));
+    identify_basins(&mut map, heightmap.width, heightmap.height, logger);
+    drop(basin_span);
+
+    // Pass 4: Flow accumulation (drainage area), also over the final flow field.
+    let accum_span = logger.map(|l| l.start_span("flow_accumulation"));
+    compute_flow_accumulation(&mut map, &filled, heightmap.width, heightmap.height, mode);
+    drop(accum_span);
 
     map
 }
 
-fn calc_slope_and_flow(hm: &Heightmap, x: u32, y: u32) -> (i16, u8) {
-    let center_h = match hm.get(x, y) {
-        Some(h) => h,
-        None => return (0, 0),
-    };
+// Multiple-flow-direction pass: for each cell, route to every neighbor
+// strictly lower than it, weighted by `(drop/dist)^p` normalized so the
+// weights across all receivers sum to 1. Cells with no lower neighbor
+// (pits, flats) are left zeroed; they fall back to the D8 pit behavior.
+fn compute_mfd(map: &mut WeatherMap, filled: &[i16], width: u32, height: u32, p: f32) {
+    let size = (width * height) as usize;
+    map.mfd_receivers = vec![[0u8; 8]; size];
+    map.mfd_weights = vec![[0f32; 8]; size];
+
+    for y in 0..height {
+        for x in 0..width {
+            let idx = (y * width + x) as usize;
+            let center = filled[idx];
+
+            let mut slots = [(0u8, 0f32); 8];
+            let mut count = 0usize;
+            let mut weight_sum = 0f32;
+
+            for dir in 1..=8usize {
+                let nx = x as i32 + DX[dir];
+                let ny = y as i32 + DY[dir];
+
+                if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                    continue;
+                }
+
+                let n_idx = (ny as u32 * width + nx as u32) as usize;
+                let nh = filled[n_idx];
+                if nh >= center {
+                    continue;
+                }
+
+                let drop = (center - nh) as f32;
+                let dist = if dir % 2 != 0 { 1.0 } else { 1.41421356 };
+                let s = drop / dist;
+                let w = s.powf(p);
+                if w <= 0.0 {
+                    continue;
+                }
+
+                slots[count] = (dir as u8, w);
+                weight_sum += w;
+                count += 1;
+            }
+
+            for i in 0..count {
+                let (dir, w) = slots[i];
+                map.mfd_receivers[idx][i] = dir;
+                map.mfd_weights[idx][i] = w / weight_sum;
+            }
+        }
+    }
+}
+
+// Flow-accumulation / drainage-area pass. Cells are visited in descending
+// elevation order (a valid topological order for a D8 flow field with a
+// strictly monotone filled surface) so that by the time a cell is visited,
+// everything that drains into it has already added its contribution.
+fn compute_flow_accumulation(
+    map: &mut WeatherMap,
+    filled: &[i16],
+    width: u32,
+    height: u32,
+    mode: FlowMode,
+) {
+    let size = (width * height) as usize;
+    map.accum = vec![1u32; size];
+
+    let mut order: Vec<usize> = (0..size).collect();
+    order.sort_unstable_by(|&a, &b| filled[b].cmp(&filled[a]));
+
+    match mode {
+        FlowMode::D8 => compute_flow_accumulation_d8(map, width, height, &order),
+        FlowMode::Mfd { .. } => compute_flow_accumulation_mfd(map, width, height, &order),
+    }
+}
+
+fn compute_flow_accumulation_d8(map: &mut WeatherMap, width: u32, height: u32, order: &[usize]) {
+    for &idx in order {
+        let flow_dir = map.flow[idx];
+        if flow_dir == 0 {
+            continue; // sink: nothing further downstream to pass flow to
+        }
+
+        let cx = (idx as u32) % width;
+        let cy = (idx as u32) / width;
+        let dir = flow_dir as usize;
+
+        let nx = cx as i32 + DX[dir];
+        let ny = cy as i32 + DY[dir];
+
+        if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+            continue;
+        }
+
+        let n_idx = (ny as u32 * width + nx as u32) as usize;
+        map.accum[n_idx] += map.accum[idx];
+    }
+}
+
+fn compute_flow_accumulation_mfd(map: &mut WeatherMap, width: u32, height: u32, order: &[usize]) {
+    // Accumulate in f32 and round once at the end. Rounding each cell's
+    // share up to at least 1 unit (as a naive per-slot round would) double-
+    // and triple-counts flow on every multi-receiver cell, since a cell
+    // with accum = 1 splitting across two receivers would otherwise emit
+    // 1 + 1 = 2 units instead of 1; accumulating fractionally keeps the
+    // total conserved and only loses a fraction of a unit to rounding once,
+    // at the very end.
+    let size = (width * height) as usize;
+    let mut accum_f32: Vec<f32> = vec![1.0; size];
+
+    for &idx in order {
+        let receivers = map.mfd_receivers[idx];
+        let weights = map.mfd_weights[idx];
+        let cx = (idx as u32) % width;
+        let cy = (idx as u32) / width;
+        let own_accum = accum_f32[idx];
+
+        for slot in 0..8 {
+            let dir = receivers[slot];
+            if dir == 0 {
+                continue;
+            }
+
+            let nx = cx as i32 + DX[dir as usize];
+            let ny = cy as i32 + DY[dir as usize];
+
+            if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                continue;
+            }
+
+            let n_idx = (ny as u32 * width + nx as u32) as usize;
+            accum_f32[n_idx] += own_accum * weights[slot];
+        }
+    }
+
+    for (dst, &val) in map.accum.iter_mut().zip(accum_f32.iter()) {
+        *dst = val.round() as u32;
+    }
+}
+
+// Slope/flow over the depression-filled surface. `filled[idx] >= original
+// height[idx]` everywhere, and strictly increases across a fill by at least
+// FILL_EPSILON, so steepest-descent here always finds a downhill neighbor
+// except at real map-edge outlets.
+fn calc_slope_and_flow_filled(hm: &Heightmap, filled: &[i16], x: u32, y: u32) -> (i16, u8) {
+    let width = hm.width;
+    let idx = (y * width + x) as usize;
+    let center_h = filled[idx];
 
     let mut best_flow = 0;
     let mut max_drop_val = 0.0;
-    let mut max_diff_abs = 0; // For slope magnitude
+    let mut max_diff_abs = 0;
 
-    // Iterate 1..=8 neighbors
     for dir in 1..=8 {
-        let nx = (x as i32 + DX[dir]);
-        let ny = (y as i32 + DY[dir]);
+        let nx = x as i32 + DX[dir];
+        let ny = y as i32 + DY[dir];
 
-        // Bounds check
         if nx < 0 || ny < 0 || nx >= hm.width as i32 || ny >= hm.height as i32 {
             continue;
         }
 
-        if let Some(nh) = hm.get(nx as u32, ny as u32) {
-            let diff = center_h as f32 - nh as f32;
-            let abs_diff = (center_h - nh).abs();
-            
-            if abs_diff > max_diff_abs {
-                max_diff_abs = abs_diff;
-            }
+        let n_idx = (ny as u32 * width + nx as u32) as usize;
+        let nh = filled[n_idx];
+        let diff = center_h as f32 - nh as f32;
+        let abs_diff = (center_h - nh).abs();
 
-            // Flow calculation (Steepest Descent)
-            // Distance: 1.0 for cardinal (odd dir), sqrt(2) for diagonal (even dir)
-            // wait: 1=N (0,-1) len 1.
-            // 2=NE (1,-1) len 1.414.
-            
-            let dist = if dir % 2 != 0 { 1.0 } else { 1.41421356 };
-            let drop_rate = diff / dist;
-
-            if drop_rate > max_drop_val {
-                max_drop_val = drop_rate;
-                best_flow = dir as u8;
-            }
+        if abs_diff > max_diff_abs {
+            max_diff_abs = abs_diff;
+        }
+
+        let dist = if dir % 2 != 0 { 1.0 } else { 1.41421356 };
+        let drop_rate = diff / dist;
+
+        if drop_rate > max_drop_val {
+            max_drop_val = drop_rate;
+            best_flow = dir as u8;
         }
     }
 
-    // Slope: spec says "Derived from height differences". 
-    // We'll use max absolute difference as a robust integer metric for now.
-    // Or should it be max_drop_val * some_scalar?
-    // Let's use max_diff_abs directly as i16.
     (max_diff_abs, best_flow)
 }
 
-fn identify_basins(map: &mut WeatherMap, width: u32, height: u32) {
-    let mut next_basin_id = 1;
-    let size = (width * height) as usize;
-    
-    // We use an iterative approach with path splitting or similar to avoid recursion depth issues
-    // But since it's a DAG (mostly), we can trace.
-    // Ideally, iterate all cells. If not basin assigned, trace flow.
-    // If sink reached, assign basin ID.
-    // If trace hits already assigned path, copy basin ID.
-    
-    // Need a way to resolve stack.
-    
-    for i in 0..size {
-        if map.basin[i] != 0 {
-            continue; // Already assigned
+// Union-find (disjoint-set) over cell indices, used to group every cell
+// with the sink or edge-outlet it drains into. Path halving on `find` keeps
+// repeated lookups near-linear even across a map-spanning union chain.
+struct UnionFind {
+    parent: Vec<u32>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size as u32).collect(),
         }
+    }
 
-        let mut path = Vec::new();
-        let mut curr = i;
-        
-        loop {
-            if map.basin[curr] != 0 {
-                // Found an existing basin, propagate it back
-                let found_id = map.basin[curr];
-                for &node in &path {
-                    map.basin[node] = found_id;
-                }
-                break;
-            }
+    fn find(&mut self, mut x: u32) -> u32 {
+        while self.parent[x as usize] != x {
+            let grandparent = self.parent[self.parent[x as usize] as usize];
+            self.parent[x as usize] = grandparent;
+            x = grandparent;
+        }
+        x
+    }
 
-            path.push(curr);
-
-            let flow_dir = map.flow[curr];
-            if flow_dir == 0 {
-                // Found a sink (and it wasn't assigned yet, otherwise map.basin[curr] != 0)
-                // Assign new basin ID
-                let new_id = next_basin_id;
-                next_basin_id += 1;
-                map.basin[curr] = new_id;
-                
-                // Propagate
-                for &node in &path {
-                    map.basin[node] = new_id;
-                }
-                break;
-            }
+    fn union(&mut self, a: u32, b: u32) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a != root_b {
+            self.parent[root_a as usize] = root_b;
+        }
+    }
+}
 
-            // Move to neighbor
-            let cx = (curr as u32) % width;
-            let cy = (curr as u32) / width;
-            let dir = flow_dir as usize;
-            
-            let nx = cx as i32 + DX[dir];
-            let ny = cy as i32 + DY[dir];
+// Groups every cell with the sink (or flat/pit it routes into) it drains
+// to, by unioning each cell with its D8 downstream neighbor. Flats are
+// already routed downhill by the depression fill that `map.flow` was
+// computed against, so the only cells left without a receiver here are
+// genuine sinks and map-edge outlets; each becomes the root of its basin.
+fn identify_basins(map: &mut WeatherMap, width: u32, height: u32, logger: Option<&StageLogger>) {
+    let size = (width * height) as usize;
+    let mut union_find = UnionFind::new(size);
 
-            // Safety check (should be safe by flow generation logic, but strictly...)
-            if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
-                 // Boundary error? Treat as sink.
-                 let new_id = next_basin_id;
-                 next_basin_id += 1;
-                 map.basin[curr] = new_id;
-                 for &node in &path {
-                    map.basin[node] = new_id;
-                }
-                break;
-            }
+    let mut sink_count: u32 = 0;
+    for idx in 0..size {
+        let flow_dir = map.flow[idx];
+        if flow_dir == 0 {
+            sink_count += 1;
+            continue;
+        }
+
+        let cx = (idx as u32) % width;
+        let cy = (idx as u32) / width;
+        let dir = flow_dir as usize;
+        let nx = cx as i32 + DX[dir];
+        let ny = cy as i32 + DY[dir];
 
-            let next_idx = (ny * width as i32 + nx) as usize;
-            
-            // Cycle detection (simple: if next_idx is in path)
-            // Since D8 with strictly positive drop avoids cycles, loop is impossible unless flat area handling produced cycles.
-            // Our calc_slope_and_flow only flows if drop > 0 (strictly positive). 
-            // So no cycles possible. Flat areas return flow 0 (sink).
-            
-            curr = next_idx;
+        if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+            sink_count += 1; // edge outlet: treat like a sink root
+            continue;
         }
+
+        let n_idx = (ny as u32 * width + nx as u32) as usize;
+        union_find.union(idx as u32, n_idx as u32);
+    }
+
+    let mut basin_id_of_root: HashMap<u32, u32> = HashMap::new();
+    let mut next_basin_id: u32 = 1;
+    for idx in 0..size {
+        let root = union_find.find(idx as u32);
+        let basin_id = *basin_id_of_root.entry(root).or_insert_with(|| {
+            let id = next_basin_id;
+            next_basin_id += 1;
+            id
+        });
+        map.basin[idx] = basin_id;
+    }
+
+    if let Some(logger) = logger {
+        logger.info(
+            "basin_labeling",
+            &format!(
+                "{} sinks/edge-outlets, {} basins over {} cells",
+                sink_count,
+                next_basin_id - 1,
+                size
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Regression test for the MFD accumulation bug: splitting a cell's
+    // accumulated flow across two receivers with uneven weights used to
+    // round each share up to at least 1 unit, so a 0.9/0.1 split emitted
+    // 1 + 1 = 2 units downstream instead of conserving the source cell's
+    // single unit. With fractional accumulation, the receiver that only
+    // gets a 0.1 share should NOT pick up a full extra unit.
+    #[test]
+    fn mfd_accumulation_conserves_flow_on_uneven_split() {
+        let width = 2;
+        let height = 2;
+        let mut map = WeatherMap::new(width, height);
+
+        // Cell (0, 0) sends 90% of its flow east to (1, 0) and 10% south
+        // to (0, 1); neither of those two cells passes flow any further.
+        map.mfd_receivers[0][0] = 3; // east
+        map.mfd_weights[0][0] = 0.9;
+        map.mfd_receivers[0][1] = 5; // south
+        map.mfd_weights[0][1] = 0.1;
+
+        let order = [0usize, 1, 2, 3];
+        compute_flow_accumulation_mfd(&mut map, width, height, &order);
+
+        // (1, 0) = idx 1 gets its own unit plus a 0.9 share -> rounds to 2.
+        assert_eq!(map.accum[1], 2);
+        // (0, 1) = idx 2 gets its own unit plus only a 0.1 share -> still 1,
+        // not the 2 the old "round each slot up to 1" bug would have given.
+        assert_eq!(map.accum[2], 1);
     }
 }
@@ -0,0 +1,77 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::analysis::{DX, DY};
+use crate::heightmap::Heightmap;
+
+// Minimum elevation gain enforced when the priority-flood crosses a pit or
+// flat region, so the filled surface always has a strict downhill gradient
+// toward the spill point instead of a dead-flat plateau.
+const FILL_EPSILON: i16 = 1;
+
+/// Priority-flood (Barnes/Planchon-Darboux) depression filling.
+///
+/// Returns a filled elevation surface where every interior pit has been
+/// raised to its spill level, so steepest-descent flow routing over the
+/// result only ever terminates at the map border. The original heightmap
+/// is left untouched; callers that need real (unfilled) heights for
+/// rendering or erosion should keep a reference to it separately.
+pub fn fill_depressions(hm: &Heightmap) -> Vec<i16> {
+    let width = hm.width;
+    let height = hm.height;
+    let size = (width * height) as usize;
+
+    let mut filled = vec![i16::MAX; size];
+    let mut in_queue = vec![false; size];
+    let mut heap: BinaryHeap<Reverse<(i16, usize)>> = BinaryHeap::new();
+
+    // Seed the queue with every border cell at its real height.
+    for y in 0..height {
+        for x in 0..width {
+            let on_border = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+            if !on_border {
+                continue;
+            }
+
+            let idx = (y * width + x) as usize;
+            let h = hm.get(x, y).expect("border coordinates are always in bounds");
+            filled[idx] = h;
+            in_queue[idx] = true;
+            heap.push(Reverse((h, idx)));
+        }
+    }
+
+    while let Some(Reverse((elev, idx))) = heap.pop() {
+        let cx = (idx as u32) % width;
+        let cy = (idx as u32) / width;
+
+        for dir in 1..=8 {
+            let nx = cx as i32 + DX[dir];
+            let ny = cy as i32 + DY[dir];
+
+            if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                continue;
+            }
+
+            let n_idx = (ny as u32 * width + nx as u32) as usize;
+            if in_queue[n_idx] {
+                continue;
+            }
+
+            let original = hm
+                .get(nx as u32, ny as u32)
+                .expect("neighbor coordinates were bounds-checked above");
+
+            // Either the real height (if it's already above the spill
+            // level) or the spill level nudged up by epsilon, whichever is
+            // higher. This guarantees filled[n] > filled[c] across a flat.
+            let candidate = original.max(elev.saturating_add(FILL_EPSILON));
+
+            filled[n_idx] = candidate;
+            in_queue[n_idx] = true;
+            heap.push(Reverse((candidate, n_idx)));
+        }
+    }
+
+    filled
+}
@@ -0,0 +1,94 @@
+use crate::analysis::{generate_weather_map, DX, DY};
+use crate::heightmap::Heightmap;
+
+/// Tunables for the detachment-limited stream-power erosion law
+/// `dz = K * A^m * S^n * dt`.
+#[derive(Debug, Clone, Copy)]
+pub struct ErosionParams {
+    pub k: f32,
+    pub m: f32,
+    pub n: f32,
+    pub dt: f32,
+    pub steps: u32,
+    /// Cells at or below this height are treated as already at base level
+    /// and never eroded, so the coastline doesn't keep digging downward.
+    pub sea_level: i16,
+}
+
+impl Default for ErosionParams {
+    fn default() -> Self {
+        Self {
+            k: 0.01,
+            m: 0.5,
+            n: 1.0,
+            dt: 1.0,
+            steps: 50,
+            sea_level: 0,
+        }
+    }
+}
+
+/// Carve valleys into `hm` using the stream-power law, driven by a fresh
+/// D8 flow/drainage-area field recomputed every step (the terrain itself
+/// changes as it erodes, so the flow field has to keep up).
+pub fn erode(hm: &mut Heightmap, params: ErosionParams) {
+    let width = hm.width;
+    let height = hm.height;
+    let size = (width * height) as usize;
+
+    for _ in 0..params.steps {
+        // Depression fill + D8 flow + drainage area, recomputed on the
+        // current (partially eroded) terrain.
+        let map = generate_weather_map(hm);
+
+        let mut dz = vec![0f32; size];
+
+        for idx in 0..size {
+            let h = hm.data[idx];
+            if h <= params.sea_level {
+                continue;
+            }
+
+            let flow_dir = map.flow[idx];
+            if flow_dir == 0 {
+                continue; // sink: no downstream cell to carve toward
+            }
+
+            let cx = (idx as u32) % width;
+            let cy = (idx as u32) / width;
+            let dir = flow_dir as usize;
+
+            let nx = cx as i32 + DX[dir];
+            let ny = cy as i32 + DY[dir];
+
+            if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                continue;
+            }
+
+            let n_idx = (ny as u32 * width + nx as u32) as usize;
+            let receiver_h = hm.data[n_idx];
+
+            let max_drop = (h - receiver_h).max(0) as f32;
+            if max_drop <= 0.0 {
+                continue;
+            }
+
+            let dist = if dir % 2 != 0 { 1.0 } else { 1.41421356 };
+            let slope = max_drop / dist;
+            let drainage_area = map.accum[idx] as f32;
+
+            let lowering =
+                params.k * drainage_area.powf(params.m) * slope.powf(params.n) * params.dt;
+
+            // Clamp so a cell never drops below its receiver: that would
+            // invert the slope and break the flow field next step.
+            dz[idx] = lowering.min(max_drop);
+        }
+
+        for idx in 0..size {
+            if dz[idx] > 0.0 {
+                hm.data[idx] -= dz[idx].round() as i16;
+            }
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+
+use crate::analysis::{DX, DY};
+use crate::heightmap::Heightmap;
+
+/// Garbrecht-Martz flat resolution.
+///
+/// `receiver` holds a D8 direction per cell (0 meaning "no lower
+/// neighbor", i.e. a candidate sink) computed by plain steepest descent on
+/// the raw heightmap. Connected groups of equal-height cells that have no
+/// lower neighbor anywhere in the group become one artificial basin per
+/// cell unless they're resolved here. For every such flat that does have
+/// at least one outlet (an edge cell draining to a strictly lower
+/// neighbor outside the flat), this assigns every interior cell a
+/// direction that descends a synthetic micro-gradient toward that outlet.
+/// Flats with no outlet at all are left untouched - they're genuine
+/// enclosed depressions for the basin/lake routing stage to handle.
+pub fn resolve_flats(hm: &Heightmap, receiver: &mut [u8]) {
+    let width = hm.width;
+    let height = hm.height;
+    let size = (width * height) as usize;
+
+    let mut visited = vec![false; size];
+
+    for start in 0..size {
+        if visited[start] || receiver[start] != 0 {
+            continue;
+        }
+
+        let flat_height = hm.data[start];
+        let members = collect_flat(hm, start, flat_height, &mut visited);
+
+        resolve_one_flat(hm, receiver, &members, flat_height, width, height);
+    }
+}
+
+/// 8-connected flood fill over cells at exactly `flat_height`, starting
+/// from `start`. Marks every visited cell so the outer loop doesn't
+/// re-process it as its own component.
+fn collect_flat(
+    hm: &Heightmap,
+    start: usize,
+    flat_height: i16,
+    visited: &mut [bool],
+) -> Vec<usize> {
+    let width = hm.width;
+    let height = hm.height;
+
+    let mut members = Vec::new();
+    let mut queue = VecDeque::new();
+
+    visited[start] = true;
+    queue.push_back(start);
+
+    while let Some(idx) = queue.pop_front() {
+        members.push(idx);
+
+        let cx = (idx as u32) % width;
+        let cy = (idx as u32) / width;
+
+        for dir in 1..=8usize {
+            let nx = cx as i32 + DX[dir];
+            let ny = cy as i32 + DY[dir];
+            if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                continue;
+            }
+
+            let n_idx = (ny as u32 * width + nx as u32) as usize;
+            if visited[n_idx] || hm.data[n_idx] != flat_height {
+                continue;
+            }
+
+            visited[n_idx] = true;
+            queue.push_back(n_idx);
+        }
+    }
+
+    members
+}
+
+fn resolve_one_flat(
+    hm: &Heightmap,
+    receiver: &mut [u8],
+    members: &[usize],
+    flat_height: i16,
+    width: u32,
+    height: u32,
+) {
+    let in_flat: std::collections::HashSet<usize> = members.iter().copied().collect();
+
+    // Outlets: flat cells that already have a strictly-lower neighbor
+    // outside the flat (their receiver was set by plain steepest descent
+    // and is nonzero).
+    let outlets: Vec<usize> = members
+        .iter()
+        .copied()
+        .filter(|&idx| receiver[idx] != 0)
+        .collect();
+
+    if outlets.is_empty() {
+        return; // genuinely enclosed flat; leave every cell as a sink
+    }
+
+    // Sources for the "away from higher ground" field: flat cells
+    // adjacent to terrain strictly above the flat's height.
+    let mut toward_high_ground = Vec::new();
+    for &idx in members {
+        let cx = (idx as u32) % width;
+        let cy = (idx as u32) / width;
+
+        let mut adjacent_to_higher = false;
+        for dir in 1..=8usize {
+            let nx = cx as i32 + DX[dir];
+            let ny = cy as i32 + DY[dir];
+            if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                continue;
+            }
+            let n_idx = (ny as u32 * width + nx as u32) as usize;
+            if hm.data[n_idx] > flat_height {
+                adjacent_to_higher = true;
+                break;
+            }
+        }
+
+        if adjacent_to_higher {
+            toward_high_ground.push(idx);
+        }
+    }
+
+    let dist_from_outlet = bfs_within(members, &in_flat, &outlets, width, height);
+    let dist_from_high_ground = bfs_within(members, &in_flat, &toward_high_ground, width, height);
+
+    // Distance-to-outlet dominates (it's what actually gets water off the
+    // flat); distance-from-higher-ground is a tie-breaker that spreads
+    // flow out instead of funneling everything down one channel.
+    let scale = (members.len() as i64) + 1;
+    let score = |idx: usize| -> i64 {
+        if receiver[idx] != 0 {
+            return i64::MIN; // outlet cells always win as a neighbor choice
+        }
+        dist_from_outlet[&idx] as i64 * scale - dist_from_high_ground[&idx] as i64
+    };
+
+    for &idx in members {
+        if receiver[idx] != 0 {
+            continue; // already has a real downhill direction
+        }
+
+        let cx = (idx as u32) % width;
+        let cy = (idx as u32) / width;
+        let own_score = score(idx);
+
+        let mut best_dir = 0u8;
+        let mut best_score = own_score;
+
+        for dir in 1..=8usize {
+            let nx = cx as i32 + DX[dir];
+            let ny = cy as i32 + DY[dir];
+            if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                continue;
+            }
+            let n_idx = (ny as u32 * width + nx as u32) as usize;
+            if !in_flat.contains(&n_idx) {
+                continue;
+            }
+
+            let neighbor_score = score(n_idx);
+            if neighbor_score < best_score {
+                best_score = neighbor_score;
+                best_dir = dir as u8;
+            }
+        }
+
+        receiver[idx] = best_dir;
+    }
+}
+
+/// Multi-source BFS distance, restricted to cells in `members`.
+fn bfs_within(
+    members: &[usize],
+    in_flat: &std::collections::HashSet<usize>,
+    sources: &[usize],
+    width: u32,
+    height: u32,
+) -> std::collections::HashMap<usize, u32> {
+    let mut dist: std::collections::HashMap<usize, u32> = std::collections::HashMap::new();
+    let mut queue = VecDeque::new();
+
+    for &s in sources {
+        dist.insert(s, 0);
+        queue.push_back(s);
+    }
+
+    while let Some(idx) = queue.pop_front() {
+        let d = dist[&idx];
+        let cx = (idx as u32) % width;
+        let cy = (idx as u32) / width;
+
+        for dir in 1..=8usize {
+            let nx = cx as i32 + DX[dir];
+            let ny = cy as i32 + DY[dir];
+            if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                continue;
+            }
+            let n_idx = (ny as u32 * width + nx as u32) as usize;
+            if !in_flat.contains(&n_idx) || dist.contains_key(&n_idx) {
+                continue;
+            }
+
+            dist.insert(n_idx, d + 1);
+            queue.push_back(n_idx);
+        }
+    }
+
+    // Any flat cell never reached (shouldn't happen since members is one
+    // connected component) defaults to the far end of the range.
+    for &idx in members {
+        dist.entry(idx).or_insert(members.len() as u32);
+    }
+
+    dist
+}
@@ -2,10 +2,39 @@ use anyhow::{ensure, Context, Result};
 use std::fs;
 use std::path::Path;
 
+// Matches HEIGHTMAP_MAGIC / HEIGHTMAP_FORMAT_VERSION in heightmap-engine.
+const MAGIC: &[u8; 4] = b"HGHT";
+const SUPPORTED_VERSION: u8 = 3;
+
+// magic(4) + version(1) + bits_per_sample(1) + width(4) + height(4) +
+// seed(8) + flags(2) + crc32c-of-sample-payload(4)
+const HEADER_SIZE: usize = 28;
+
+/// Compression applied to the on-disk sample payload, mirrored from
+/// heightmap-engine's `CompressionMode` via the header's `flags` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompressionMode {
+    None,
+    Lz4,
+    Deflate,
+}
+
+impl CompressionMode {
+    fn from_flag(flag: u16) -> Result<Self> {
+        match flag {
+            0 => Ok(CompressionMode::None),
+            1 => Ok(CompressionMode::Lz4),
+            2 => Ok(CompressionMode::Deflate),
+            other => anyhow::bail!("Unsupported compression flag {}", other),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Heightmap {
     pub width: u32,
     pub height: u32,
+    pub bits_per_sample: u8,
     pub data: Vec<i16>, // Using i16 based on "signed height values" history, though u16 also possible.
 }
 
@@ -14,36 +43,86 @@ impl Heightmap {
         let path = path.as_ref();
         let bytes = fs::read(path).with_context(|| format!("Failed to read heightmap: {:?}", path))?;
 
-        ensure!(bytes.len() >= 16, "File too small to contain header");
+        ensure!(bytes.len() >= HEADER_SIZE, "File too small to contain header");
+        ensure!(&bytes[0..4] == MAGIC, "Bad magic tag: expected {:?}, got {:?}", MAGIC, &bytes[0..4]);
 
-        // Parse header
-        let width = u32::from_le_bytes(bytes[0..4].try_into()?);
-        let height = u32::from_le_bytes(bytes[4..8].try_into()?);
-        
-        // Next 8 bytes: 8..16 (Metadata/Seed - ignored)
-        
-        let header_size = 16;
-        let expected_data_size = (width as usize) * (height as usize) * 2; // 2 bytes per pixel
-        let total_expected_size = header_size + expected_data_size;
+        let version = bytes[4];
+        ensure!(version == SUPPORTED_VERSION, "Unsupported heightmap format version {}", version);
 
+        let bits_per_sample = bytes[5];
         ensure!(
-            bytes.len() == total_expected_size,
-            "File size mismatch. Header claims {}x{} ({} bytes data), but file is {} bytes. Expected {}",
-            width, height, expected_data_size, bytes.len(), total_expected_size
+            bits_per_sample == 8 || bits_per_sample == 16,
+            "Unsupported bits_per_sample {} (expected 8 or 16)",
+            bits_per_sample
         );
 
-        // Parse data
-        let mut data = Vec::with_capacity((width * height) as usize);
-        let data_slice = &bytes[header_size..];
+        let width = u32::from_le_bytes(bytes[6..10].try_into()?);
+        let height = u32::from_le_bytes(bytes[10..14].try_into()?);
+        // bytes 14..22: random_seed (u64), not needed by this loader.
+        let compression_mode = CompressionMode::from_flag(u16::from_le_bytes(bytes[22..24].try_into()?))?;
+        let stored_checksum = u32::from_le_bytes(bytes[24..28].try_into()?);
+
+        let bytes_per_sample = (bits_per_sample / 8) as usize;
+        let expected_data_size = (width as usize) * (height as usize) * bytes_per_sample;
 
-        for chunk in data_slice.chunks_exact(2) {
-            let val = i16::from_le_bytes(chunk.try_into()?);
-            data.push(val);
+        // Anything past the sample payload (e.g. the engine's terrain
+        // layer bytes) belongs to other consumers; this loader only cares
+        // about the heightmap samples themselves. When uncompressed, the
+        // payload is exactly `expected_data_size`; when compressed, it's a
+        // u32 length prefix followed by that many compressed bytes.
+        let sample_payload: &[u8] = if compression_mode == CompressionMode::None {
+            ensure!(
+                bytes.len() >= HEADER_SIZE + expected_data_size,
+                "File too small for {}x{} at {}-bit. Expected at least {} bytes (header + samples), got {}",
+                width, height, bits_per_sample, HEADER_SIZE + expected_data_size, bytes.len()
+            );
+            &bytes[HEADER_SIZE..HEADER_SIZE + expected_data_size]
+        } else {
+            ensure!(bytes.len() >= HEADER_SIZE + 4, "File too small to contain sample payload length prefix");
+            let compressed_len = u32::from_le_bytes(bytes[HEADER_SIZE..HEADER_SIZE + 4].try_into()?) as usize;
+            ensure!(
+                bytes.len() >= HEADER_SIZE + 4 + compressed_len,
+                "File too small for a {}-byte compressed sample payload",
+                compressed_len
+            );
+            &bytes[HEADER_SIZE..HEADER_SIZE + 4 + compressed_len]
+        };
+
+        let computed_checksum = crc32c::crc32c(sample_payload);
+        ensure!(
+            computed_checksum == stored_checksum,
+            "Checksum mismatch for {:?}: file may be corrupted or truncated (expected {:08x}, got {:08x})",
+            path, stored_checksum, computed_checksum
+        );
+
+        let data_bytes: Vec<u8> = match compression_mode {
+            CompressionMode::None => sample_payload.to_vec(),
+            CompressionMode::Lz4 => lz4_flex::decompress_size_prepended(&sample_payload[4..])
+                .map_err(|e| anyhow::anyhow!("LZ4 decompress failed: {}", e))?,
+            CompressionMode::Deflate => miniz_oxide::inflate::decompress_to_vec(&sample_payload[4..])
+                .map_err(|e| anyhow::anyhow!("Deflate decompress failed: {:?}", e))?,
+        };
+        ensure!(
+            data_bytes.len() == expected_data_size,
+            "Decompressed sample size mismatch: expected {}, got {}",
+            expected_data_size, data_bytes.len()
+        );
+
+        let mut data = Vec::with_capacity((width * height) as usize);
+        if bits_per_sample == 16 {
+            for chunk in data_bytes.chunks_exact(2) {
+                data.push(i16::from_le_bytes(chunk.try_into()?));
+            }
+        } else {
+            for &sample in &data_bytes {
+                data.push(sample as i16);
+            }
         }
 
         Ok(Self {
             width,
             height,
+            bits_per_sample,
             data,
         })
     }
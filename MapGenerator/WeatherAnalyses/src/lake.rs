@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+
+use crate::analysis::{DX, DY};
+use crate::heightmap::Heightmap;
+use crate::weather_map::WeatherMap;
+
+// Node 0 in the basin graph is a virtual "ocean" node representing the
+// outside of the map. Every border-touching basin gets an edge to it
+// weighted below any real pass, so Boruvka's algorithm always roots the spanning forest there instead
+// of at an arbitrary interior basin.
+const OCEAN: u32 = 0;
+const OCEAN_EDGE_WEIGHT: i16 = i16::MIN;
+
+/// Cordonnier-Bovy-Braun (2019) linear-complexity depression routing.
+///
+/// Replaces the priority-flood's flat per-pit fill with proper
+/// hydrologically-connected basins: every endorheic basin spills into its
+/// lowest adjacent basin through the lowest pass (saddle) on their shared
+/// boundary, chosen by a minimum spanning tree over the basin graph rooted
+/// at the map border. Populates `map.lake_level`; a cell is underwater
+/// where `lake_level > height`.
+pub fn route_lakes(hm: &Heightmap, map: &mut WeatherMap) {
+    let width = hm.width;
+    let height = hm.height;
+    let size = (width * height) as usize;
+
+    let (basin_of, basin_count, sinks, receiver) = label_basins(hm);
+
+    let mut touches_border = vec![false; basin_count as usize];
+    for y in 0..height {
+        for x in 0..width {
+            if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                let idx = (y * width + x) as usize;
+                touches_border[basin_of[idx] as usize] = true;
+            }
+        }
+    }
+
+    // Lowest pass cell between every pair of adjacent basins.
+    let mut passes: HashMap<(u32, u32), (i16, usize, usize)> = HashMap::new();
+    for y in 0..height {
+        for x in 0..width {
+            let idx = (y * width + x) as usize;
+            let basin_a = basin_of[idx];
+
+            for dir in 1..=8usize {
+                let nx = x as i32 + DX[dir];
+                let ny = y as i32 + DY[dir];
+                if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                    continue;
+                }
+
+                let n_idx = (ny as u32 * width + nx as u32) as usize;
+                let basin_b = basin_of[n_idx];
+                if basin_a == basin_b {
+                    continue;
+                }
+
+                // Cells are stored in key order: the first lies in the
+                // lower-numbered basin, the second in the higher one.
+                let (key, cell_lo, cell_hi) = if basin_a < basin_b {
+                    ((basin_a, basin_b), idx, n_idx)
+                } else {
+                    ((basin_b, basin_a), n_idx, idx)
+                };
+
+                let pass_elev = hm.data[idx].max(hm.data[n_idx]);
+
+                passes
+                    .entry(key)
+                    .and_modify(|(best, a, b)| {
+                        if pass_elev < *best {
+                            *best = pass_elev;
+                            *a = cell_lo;
+                            *b = cell_hi;
+                        }
+                    })
+                    .or_insert((pass_elev, cell_lo, cell_hi));
+            }
+        }
+    }
+
+    // Edge list for the basin graph: real inter-basin passes plus the
+    // OCEAN_EDGE_WEIGHT virtual edges from every border basin to the ocean node.
+    let mut edges: Vec<(u32, u32, i16, usize, usize)> = Vec::new();
+    for (&(a, b), &(elev, cell_a, cell_b)) in &passes {
+        edges.push((a, b, elev, cell_a, cell_b));
+    }
+    for basin in 1..basin_count {
+        if touches_border[basin as usize] {
+            edges.push((OCEAN, basin, OCEAN_EDGE_WEIGHT, 0, 0));
+        }
+    }
+
+    let mst_edges = boruvka_mst(basin_count, &edges);
+
+    // Build an undirected adjacency list from the MST edges, then BFS from
+    // the ocean node to get a parent + spill-elevation for every basin.
+    let mut adjacency: Vec<Vec<(u32, i16, usize, usize)>> = vec![Vec::new(); basin_count as usize];
+    for &(a, b, elev, cell_a, cell_b) in &mst_edges {
+        adjacency[a as usize].push((b, elev, cell_a, cell_b));
+        adjacency[b as usize].push((a, elev, cell_b, cell_a));
+    }
+
+    let mut parent_basin: Vec<u32> = (0..basin_count).collect();
+    let mut spill_to_parent: Vec<i16> = vec![0; basin_count as usize];
+    let mut spill_cells: Vec<(usize, usize)> = vec![(0, 0); basin_count as usize]; // (cell in this basin, cell in parent basin)
+    let mut propagated: Vec<i16> = vec![OCEAN_EDGE_WEIGHT; basin_count as usize];
+    let mut visited = vec![false; basin_count as usize];
+
+    let mut queue = std::collections::VecDeque::new();
+    visited[OCEAN as usize] = true;
+    queue.push_back(OCEAN);
+
+    while let Some(basin) = queue.pop_front() {
+        for &(neighbor, elev, cell_self, cell_neighbor) in &adjacency[basin as usize] {
+            if visited[neighbor as usize] {
+                continue;
+            }
+            visited[neighbor as usize] = true;
+            parent_basin[neighbor as usize] = basin;
+            spill_to_parent[neighbor as usize] = elev;
+            spill_cells[neighbor as usize] = (cell_neighbor, cell_self);
+            propagated[neighbor as usize] = elev.max(propagated[basin as usize]);
+            queue.push_back(neighbor);
+        }
+    }
+
+    // Lake surface: every cell is flooded up to its basin's propagated
+    // spill elevation (a no-op for basins that drain straight to the
+    // border, since OCEAN_EDGE_WEIGHT is far below any real height).
+    map.lake_level = vec![0; size];
+    for idx in 0..size {
+        let basin = basin_of[idx];
+        map.lake_level[idx] = hm.data[idx].max(propagated[basin as usize]);
+    }
+
+    // Re-orient each non-root basin's flow so it spills toward its MST
+    // pass instead of collecting at the old sink. The pass cell (cell_self)
+    // is usually nowhere near the sink, so a single-step redirect at the
+    // sink leaves the rest of the basin's interior still draining toward
+    // a sink that no longer accepts flow — walk the basin's own
+    // steepest-descent chain from the pass back to the sink and flip every
+    // step along that path instead.
+    for basin in 1..basin_count {
+        if basin == parent_basin[basin as usize] {
+            continue; // unreachable basin (shouldn't happen on a connected grid)
+        }
+        if propagated[basin as usize] == OCEAN_EDGE_WEIGHT {
+            continue; // attached straight to the ocean node; already drains
+        }
+
+        let sink = sinks[basin as usize];
+        let (cell_self, cell_neighbor) = spill_cells[basin as usize];
+        let path = walk_to_sink(&receiver, width, sink, cell_self);
+
+        // The pass cell itself spills out of the basin, across the MST
+        // edge, into the neighboring basin's side of the pass.
+        map.flow[cell_self] = dir_toward(width, cell_self, cell_neighbor);
+
+        // Every other cell on the path (out to the sink) used to drain
+        // downhill toward the sink; flip each step so it now drains back
+        // up toward the pass instead.
+        for step in path.windows(2) {
+            let (uphill, downhill) = (step[0], step[1]);
+            map.flow[downhill] = dir_toward(width, downhill, uphill);
+        }
+    }
+}
+
+/// Walks the basin's original steepest-descent chain from `start` down to
+/// `sink`, returning the cells visited in that (downhill) order, `start`
+/// first. `receiver` is the direction grid `label_basins` built this
+/// basin's labeling from, so the walk always terminates at `sink` — that's
+/// how `label_basins` determined `start` belongs to this basin in the
+/// first place.
+fn walk_to_sink(receiver: &[u8], width: u32, sink: usize, start: usize) -> Vec<usize> {
+    let mut path = vec![start];
+    let mut current = start;
+
+    while current != sink {
+        let dir = receiver[current];
+        let cx = (current as u32) % width;
+        let cy = (current as u32) / width;
+        let nx = cx as i32 + DX[dir as usize];
+        let ny = cy as i32 + DY[dir as usize];
+        current = (ny as u32 * width + nx as u32) as usize;
+        path.push(current);
+    }
+
+    path
+}
+
+/// Label every cell with the basin of the local minimum it drains into,
+/// using plain D8 steepest descent on the raw (unfilled) heightmap. Basin
+/// ids are 1-based so 0 can be reserved as the ocean/root node. Returns
+/// the basin labeling, the number of basins (including the reserved 0
+/// slot), each basin's original sink cell index, and the per-cell
+/// steepest-descent direction grid (post-flats-resolution) used to build
+/// that labeling — `route_lakes` walks it back from a spill pass to a
+/// basin's sink to re-orient the whole interior path, not just one cell.
+fn label_basins(hm: &Heightmap) -> (Vec<u32>, u32, Vec<usize>, Vec<u8>) {
+    let width = hm.width;
+    let height = hm.height;
+    let size = (width * height) as usize;
+
+    let mut receiver = vec![0u8; size];
+    for y in 0..height {
+        for x in 0..width {
+            let idx = (y * width + x) as usize;
+            let h = hm.data[idx];
+
+            let mut best_dir = 0u8;
+            let mut best_drop = 0i32;
+
+            for dir in 1..=8usize {
+                let nx = x as i32 + DX[dir];
+                let ny = y as i32 + DY[dir];
+                if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                    continue;
+                }
+                let n_idx = (ny as u32 * width + nx as u32) as usize;
+                let drop = h as i32 - hm.data[n_idx] as i32;
+                if drop > best_drop {
+                    best_drop = drop;
+                    best_dir = dir as u8;
+                }
+            }
+
+            receiver[idx] = best_dir;
+        }
+    }
+
+    // Flats (plateaus, lakebeds, mesas) have no lower neighbor anywhere in
+    // the group under plain steepest descent, which would otherwise turn
+    // every such flat into its own spurious micro-basin. Resolve the ones
+    // that do have a real outlet before basin-labeling runs.
+    crate::flats::resolve_flats(hm, &mut receiver);
+
+    let mut basin_of = vec![0u32; size];
+    let mut sinks = vec![0usize]; // index 0 reserved for the ocean node
+    let mut next_basin_id = 1u32;
+
+    for start in 0..size {
+        if basin_of[start] != 0 {
+            continue;
+        }
+
+        let mut path = Vec::new();
+        let mut curr = start;
+
+        loop {
+            if basin_of[curr] != 0 {
+                let id = basin_of[curr];
+                for &node in &path {
+                    basin_of[node] = id;
+                }
+                break;
+            }
+
+            path.push(curr);
+
+            let dir = receiver[curr];
+            if dir == 0 {
+                let id = next_basin_id;
+                next_basin_id += 1;
+                sinks.push(curr);
+                basin_of[curr] = id;
+                for &node in &path {
+                    basin_of[node] = id;
+                }
+                break;
+            }
+
+            let cx = (curr as u32) % width;
+            let cy = (curr as u32) / width;
+            let nx = cx as i32 + DX[dir as usize];
+            let ny = cy as i32 + DY[dir as usize];
+            curr = (ny as u32 * width + nx as u32) as usize;
+        }
+    }
+
+    (basin_of, next_basin_id, sinks, receiver)
+}
+
+/// Boruvka's algorithm: repeatedly contract the single cheapest outgoing
+/// edge of every component, round after round, until one component
+/// remains. Near-linear in edge count since each round at least halves
+/// the number of components.
+fn boruvka_mst(
+    node_count: u32,
+    edges: &[(u32, u32, i16, usize, usize)],
+) -> Vec<(u32, u32, i16, usize, usize)> {
+    let mut parent: Vec<u32> = (0..node_count).collect();
+    let mut mst = Vec::new();
+    let mut components_remaining = node_count;
+
+    while components_remaining > 1 {
+        // Cheapest outgoing edge seen so far per component, by edge index.
+        let mut cheapest: HashMap<u32, usize> = HashMap::new();
+
+        for (i, &(a, b, elev, _, _)) in edges.iter().enumerate() {
+            let ca = find(&mut parent, a);
+            let cb = find(&mut parent, b);
+            if ca == cb {
+                continue;
+            }
+
+            for &comp in &[ca, cb] {
+                let better = match cheapest.get(&comp) {
+                    Some(&existing) => elev < edges[existing].2,
+                    None => true,
+                };
+                if better {
+                    cheapest.insert(comp, i);
+                }
+            }
+        }
+
+        if cheapest.is_empty() {
+            break; // graph is disconnected; nothing more to contract
+        }
+
+        let mut merged_this_round = false;
+        for &edge_idx in cheapest.values() {
+            let (a, b, elev, cell_a, cell_b) = edges[edge_idx];
+            let ca = find(&mut parent, a);
+            let cb = find(&mut parent, b);
+            if ca == cb {
+                continue; // already joined by another component's pick this round
+            }
+
+            parent[ca as usize] = cb;
+            mst.push((a, b, elev, cell_a, cell_b));
+            components_remaining -= 1;
+            merged_this_round = true;
+        }
+
+        if !merged_this_round {
+            break;
+        }
+    }
+
+    mst
+}
+
+fn find(parent: &mut [u32], x: u32) -> u32 {
+    if parent[x as usize] != x {
+        let root = find(parent, parent[x as usize]);
+        parent[x as usize] = root;
+    }
+    parent[x as usize]
+}
+
+/// Nearest D8 direction from `from` to `to`, used when re-orienting the
+/// cells along a basin's spill path. Every caller passes two adjacent
+/// cells, so the result points exactly at `to`.
+fn dir_toward(width: u32, from: usize, to: usize) -> u8 {
+    let fx = (from as u32 % width) as i32;
+    let fy = (from as u32 / width) as i32;
+    let tx = (to as u32 % width) as i32;
+    let ty = (to as u32 / width) as i32;
+
+    let dx = (tx - fx).signum();
+    let dy = (ty - fy).signum();
+
+    for dir in 1..=8usize {
+        if DX[dir] == dx && DY[dir] == dy {
+            return dir as u8;
+        }
+    }
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Regression test for the lake-routing reorientation fix: a non-root
+    // basin's interior path from its spill cell back to its original sink
+    // must be walked and reversed in full, not just the one cell adjacent
+    // to the pass. `walk_to_sink` is the piece that finds that path; if it
+    // stopped early or misread a direction the reorientation loop in
+    // `route_lakes` would leave a 2-cycle between the sink and its
+    // neighbor instead of draining the whole basin outward.
+    #[test]
+    fn walk_to_sink_follows_the_full_receiver_chain() {
+        // A 5-wide, 1-tall row where every cell points west (dir 7) at its
+        // neighbor, except the sink itself (index 0, dir 0).
+        let receiver: Vec<u8> = vec![0, 7, 7, 7, 7];
+        let width = 5;
+
+        let path = walk_to_sink(&receiver, width, 0, 4);
+
+        assert_eq!(path, vec![4, 3, 2, 1, 0]);
+    }
+}
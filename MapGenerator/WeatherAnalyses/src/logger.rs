@@ -3,11 +3,11 @@
 //! Writes structured logs to `logs/jobs/{job_id}/weather.log.jsonl`
 //! in the same format as Tiler, so mapgenctl TUI can display them.
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fs::{self, OpenOptions};
 use std::io::Write;
 use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 /// A structured log entry matching the Tiler's JSONL format.
 #[derive(Serialize)]
@@ -89,4 +89,95 @@ impl StageLogger {
             }
         }
     }
+
+    /// Start a timed span. The span is recorded (as a Chrome Trace Event
+    /// "complete" event) to the companion `weather.trace.json` file the
+    /// moment the returned guard is dropped, so callers just wrap the code
+    /// they want timed in a block scope rather than threading a "stop"
+    /// call through every early return.
+    pub fn start_span(&self, name: impl Into<String>) -> SpanGuard<'_> {
+        SpanGuard {
+            logger: self,
+            name: name.into(),
+            start: Instant::now(),
+            start_ts_micros: now_utc_micros(),
+        }
+    }
+
+    fn trace_path(&self) -> PathBuf {
+        self.log_path.with_file_name(format!("{}.trace.json", self.stage))
+    }
+
+    fn record_span(&self, name: &str, start_ts_micros: u64, dur_micros: u64) -> anyhow::Result<()> {
+        let path = self.trace_path();
+
+        let mut events: Vec<TraceEvent> = if path.exists() {
+            let contents = fs::read_to_string(&path)?;
+            serde_json::from_str(&contents).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        events.push(TraceEvent {
+            name: name.to_string(),
+            ph: "X",
+            ts: start_ts_micros,
+            dur: dur_micros,
+            pid: job_hash(&self.job_id),
+            tid: job_hash(&self.stage),
+        });
+
+        let json = serde_json::to_string(&events)?;
+        fs::write(&path, json)?;
+        Ok(())
+    }
+}
+
+fn now_utc_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+// Stable numeric id for a span's "pid"/"tid" fields, since the Chrome Trace
+// Event format expects numbers there rather than the job id / stage name
+// strings we actually have.
+fn job_hash(value: &str) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// One entry in the Chrome Trace Event array written to `weather.trace.json`.
+/// `ph: "X"` marks a "complete" event (a span with both a start and a
+/// duration), the form `chrome://tracing` and Perfetto render as a bar.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TraceEvent {
+    name: String,
+    ph: &'static str,
+    ts: u64,
+    dur: u64,
+    pid: u64,
+    tid: u64,
+}
+
+/// Guard returned by `StageLogger::start_span`. Records the span's timing
+/// to the trace file when dropped, so a span is simply the guard's scope.
+pub struct SpanGuard<'a> {
+    logger: &'a StageLogger,
+    name: String,
+    start: Instant,
+    start_ts_micros: u64,
+}
+
+impl Drop for SpanGuard<'_> {
+    fn drop(&mut self) {
+        let dur_micros = self.start.elapsed().as_micros() as u64;
+        // Best-effort: a failed trace write shouldn't panic mid-drop and
+        // take down whatever stage was being timed.
+        let _ = self.logger.record_span(&self.name, self.start_ts_micros, dur_micros);
+    }
 }
@@ -10,11 +10,21 @@ struct Args {
 
     #[arg(long)]
     output: PathBuf,
+
+    /// Per-layer compression for the output file: none, lz4, or deflate.
+    #[arg(long, default_value = "none")]
+    compress: String,
 }
 
 mod heightmap;
 mod weather_map;
 mod analysis;
+mod depression;
+mod erosion;
+mod lake;
+mod flats;
+mod drain;
+mod logger;
 
 fn main() {
     let args = Args::parse();
@@ -34,13 +44,32 @@ fn run(args: &Args) -> anyhow::Result<()> {
     let hm = heightmap::Heightmap::load(&args.input)?;
     println!("Loaded heightmap: {}x{}", hm.width, hm.height);
 
+    let job_id = args
+        .input
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("weather")
+        .to_string();
+    let stage_logger = logger::StageLogger::new(&job_id)?;
+
     // Perform analysis
     println!("Generating weather analysis...");
-    let weather = analysis::generate_weather_map(&hm);
+    let weather = analysis::generate_weather_map_with_mode_and_logger(
+        &hm,
+        analysis::FlowMode::D8,
+        Some(&stage_logger),
+    );
+
+    let compression = match args.compress.as_str() {
+        "none" => weather_map::CompressionMode::None,
+        "lz4" => weather_map::CompressionMode::Lz4,
+        "deflate" => weather_map::CompressionMode::Deflate,
+        other => anyhow::bail!("Unknown --compress mode {:?} (expected none, lz4, or deflate)", other),
+    };
 
     // Write output
     println!("Saving weather map...");
-    weather.save(&args.output)?;
-    
+    weather.save_with_compression(&args.output, compression, Some(&stage_logger))?;
+
     Ok(())
 }
@@ -1,11 +1,46 @@
-use anyhow::{Context, Result};
-use std::fs::File;
+use anyhow::{ensure, Context, Result};
+use std::fs::{self, File};
 use std::io::{BufWriter, Write};
 use std::path::Path;
 
+use crate::logger::StageLogger;
+
 pub const MAGIC: u32 = 0x57414E41; // "WANA" (Weather ANAlyses) - Made up for now, spec didn't specify value constant
 pub const VERSION: u16 = 1;
 
+// magic(4) + version(2) + width(4) + height(4) + layer_count(2) + flags(2) + crc32c(4)
+const HEADER_SIZE: usize = 22;
+
+/// Per-layer compression applied when writing a `WeatherMap`, recorded in
+/// the header's `flags` field so `load` knows how to undo it. `None` is
+/// the default so files written by older callers of `save` stay readable
+/// without any decompression step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionMode {
+    None,
+    Lz4,
+    Deflate,
+}
+
+impl CompressionMode {
+    fn flag(self) -> u16 {
+        match self {
+            CompressionMode::None => 0,
+            CompressionMode::Lz4 => 1,
+            CompressionMode::Deflate => 2,
+        }
+    }
+
+    fn from_flag(flag: u16) -> Result<Self> {
+        match flag {
+            0 => Ok(CompressionMode::None),
+            1 => Ok(CompressionMode::Lz4),
+            2 => Ok(CompressionMode::Deflate),
+            other => anyhow::bail!("Unsupported compression flag {}", other),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct WeatherMap {
     pub width: u32,
@@ -13,6 +48,17 @@ pub struct WeatherMap {
     pub slope: Vec<i16>,     // Layer 1
     pub flow: Vec<u8>,       // Layer 2
     pub basin: Vec<u32>,     // Layer 3
+    pub accum: Vec<u32>,     // Layer 4: drainage area (cell count upstream, inclusive)
+
+    // MFD (multiple-flow-direction) receivers, fixed width 8 per cell.
+    // Unused slots are zeroed. Only populated when `generate_weather_map_with_mode`
+    // is run in `FlowMode::Mfd`; otherwise left at their zeroed defaults.
+    pub mfd_receivers: Vec<[u8; 8]>,
+    pub mfd_weights: Vec<[f32; 8]>,
+
+    /// Lake surface elevation per cell, from Cordonnier-style basin
+    /// routing. A cell is underwater where `lake_level > height`.
+    pub lake_level: Vec<i16>,
 }
 
 impl WeatherMap {
@@ -24,11 +70,104 @@ impl WeatherMap {
             slope: vec![0; size],
             flow: vec![0; size],
             basin: vec![0; size],
+            accum: vec![1; size],
+            mfd_receivers: vec![[0; 8]; size],
+            mfd_weights: vec![[0.0; 8]; size],
+            lake_level: vec![0; size],
         }
     }
 
+    /// Tag cells whose drainage area meets or exceeds `threshold` as
+    /// river/stream cells. A higher threshold keeps only the major
+    /// channels; a lower one also picks up small tributaries.
+    pub fn river_mask(&self, threshold: u32) -> Vec<bool> {
+        self.accum.iter().map(|&a| a >= threshold).collect()
+    }
+
+    fn layer_bytes(&self) -> (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>) {
+        let mut slope_bytes = Vec::with_capacity(self.slope.len() * 2);
+        for &val in &self.slope {
+            slope_bytes.extend_from_slice(&val.to_le_bytes());
+        }
+
+        let mut basin_bytes = Vec::with_capacity(self.basin.len() * 4);
+        for &val in &self.basin {
+            basin_bytes.extend_from_slice(&val.to_le_bytes());
+        }
+
+        let mut accum_bytes = Vec::with_capacity(self.accum.len() * 4);
+        for &val in &self.accum {
+            accum_bytes.extend_from_slice(&val.to_le_bytes());
+        }
+
+        let mut lake_level_bytes = Vec::with_capacity(self.lake_level.len() * 2);
+        for &val in &self.lake_level {
+            lake_level_bytes.extend_from_slice(&val.to_le_bytes());
+        }
+
+        (slope_bytes, basin_bytes, accum_bytes, lake_level_bytes)
+    }
+
+    /// Write a layer, optionally as a length-prefixed compressed block, and
+    /// append it to the running checksum / payload size accounting.
+    fn encode_layer(mode: CompressionMode, raw: &[u8], payload: &mut Vec<u8>) {
+        if mode == CompressionMode::None {
+            payload.extend_from_slice(raw);
+            return;
+        }
+
+        let compressed = match mode {
+            CompressionMode::None => unreachable!(),
+            CompressionMode::Lz4 => lz4_flex::compress_prepend_size(raw),
+            CompressionMode::Deflate => miniz_oxide::deflate::compress_to_vec(raw, 6),
+        };
+        payload.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        payload.extend_from_slice(&compressed);
+    }
+
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.save_with_compression(path, CompressionMode::None, None)
+    }
+
+    /// Same as `save`, but with an optional `CompressionMode` applied to
+    /// each layer and an optional `StageLogger` to record the pre/post byte
+    /// sizes so the compression ratio is observable. `CompressionMode::None`
+    /// keeps the original uncompressed layout so existing consumers of
+    /// `load` stay compatible.
+    pub fn save_with_compression<P: AsRef<Path>>(
+        &self,
+        path: P,
+        mode: CompressionMode,
+        logger: Option<&StageLogger>,
+    ) -> Result<()> {
         let path = path.as_ref();
+        let (slope_bytes, basin_bytes, accum_bytes, lake_level_bytes) = self.layer_bytes();
+        let raw_layers: [&[u8]; 5] = [&slope_bytes, &self.flow, &basin_bytes, &accum_bytes, &lake_level_bytes];
+        let raw_size: usize = raw_layers.iter().map(|layer| layer.len()).sum();
+
+        let mut payload = Vec::with_capacity(raw_size);
+        for raw in raw_layers {
+            Self::encode_layer(mode, raw, &mut payload);
+        }
+
+        if let Some(logger) = logger {
+            logger.info(
+                "weather_map_compressed",
+                &format!(
+                    "{:?}: {} bytes -> {} bytes ({:.1}% of original)",
+                    mode,
+                    raw_size,
+                    payload.len(),
+                    100.0 * payload.len() as f64 / raw_size.max(1) as f64
+                ),
+            );
+        }
+
+        // CRC32C over the on-disk payload (post-compression), so
+        // `WeatherMap::load` can detect bit rot or a truncated write
+        // before anything downstream trusts it.
+        let checksum = crc32c::crc32c(&payload);
+
         let file = File::create(path).with_context(|| format!("Failed to create output file: {:?}", path))?;
         let mut writer = BufWriter::new(file);
 
@@ -38,29 +177,134 @@ impl WeatherMap {
         // width: u32
         // height: u32
         // layer_count: u16
-        
+        // flags: u16 (compression mode: 0 = none, 1 = lz4, 2 = deflate)
+        // checksum: u32 (crc32c of the payload below)
+
         writer.write_all(&MAGIC.to_le_bytes())?;
         writer.write_all(&VERSION.to_le_bytes())?;
         writer.write_all(&self.width.to_le_bytes())?;
         writer.write_all(&self.height.to_le_bytes())?;
-        
-        let layer_count: u16 = 3;
+
+        let layer_count: u16 = 5;
         writer.write_all(&layer_count.to_le_bytes())?;
+        writer.write_all(&mode.flag().to_le_bytes())?;
+        writer.write_all(&checksum.to_le_bytes())?;
 
-        // Layer 1: Slope (i16)
-        for &val in &self.slope {
-            writer.write_all(&val.to_le_bytes())?;
-        }
+        writer.write_all(&payload)?;
 
-        // Layer 2: Flow (u8)
-        writer.write_all(&self.flow)?;
+        writer.flush()?;
+        Ok(())
+    }
 
-        // Layer 3: Basin (u32)
-        for &val in &self.basin {
-            writer.write_all(&val.to_le_bytes())?;
+    /// Load a `WeatherMap` previously written by `save` or
+    /// `save_with_compression`. MFD receivers and weights aren't persisted
+    /// to disk, so they come back zero-filled; callers that need them
+    /// should re-run `generate_weather_map_with_mode` in `FlowMode::Mfd`
+    /// instead of loading.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).with_context(|| format!("Failed to read weather map: {:?}", path))?;
+
+        ensure!(bytes.len() >= HEADER_SIZE, "File too small to contain header");
+
+        let magic = u32::from_le_bytes(bytes[0..4].try_into()?);
+        ensure!(magic == MAGIC, "Bad magic tag: expected {:#x}, got {:#x}", MAGIC, magic);
+
+        let version = u16::from_le_bytes(bytes[4..6].try_into()?);
+        ensure!(version == VERSION, "Unsupported weather map format version {}", version);
+
+        let width = u32::from_le_bytes(bytes[6..10].try_into()?);
+        let height = u32::from_le_bytes(bytes[10..14].try_into()?);
+        let layer_count = u16::from_le_bytes(bytes[14..16].try_into()?);
+        ensure!(layer_count == 5, "Unsupported layer count {} (expected 5)", layer_count);
+        let mode = CompressionMode::from_flag(u16::from_le_bytes(bytes[16..18].try_into()?))?;
+        let stored_checksum = u32::from_le_bytes(bytes[18..22].try_into()?);
+
+        let size = (width as usize) * (height as usize);
+        let slope_size = size * 2;
+        let flow_size = size;
+        let basin_size = size * 4;
+        let accum_size = size * 4;
+        let lake_level_size = size * 2;
+        let raw_sizes = [slope_size, flow_size, basin_size, accum_size, lake_level_size];
+
+        // Walk the layer framing (fixed-size when uncompressed,
+        // length-prefixed when compressed) to find where the payload ends,
+        // without needing to decompress anything yet.
+        let mut cursor = HEADER_SIZE;
+        let mut layer_slices: Vec<&[u8]> = Vec::with_capacity(5);
+        for &raw_size in &raw_sizes {
+            if mode == CompressionMode::None {
+                ensure!(bytes.len() >= cursor + raw_size, "File too small for {}x{}: truncated layer", width, height);
+                layer_slices.push(&bytes[cursor..cursor + raw_size]);
+                cursor += raw_size;
+            } else {
+                ensure!(bytes.len() >= cursor + 4, "File too small for {}x{}: truncated layer length prefix", width, height);
+                let compressed_len = u32::from_le_bytes(bytes[cursor..cursor + 4].try_into()?) as usize;
+                cursor += 4;
+                ensure!(bytes.len() >= cursor + compressed_len, "File too small for {}x{}: truncated compressed layer", width, height);
+                layer_slices.push(&bytes[cursor..cursor + compressed_len]);
+                cursor += compressed_len;
+            }
         }
 
-        writer.flush()?;
-        Ok(())
+        let payload = &bytes[HEADER_SIZE..cursor];
+        let computed_checksum = crc32c::crc32c(payload);
+        ensure!(
+            computed_checksum == stored_checksum,
+            "Checksum mismatch for {:?}: file may be corrupted or truncated (expected {:08x}, got {:08x})",
+            path, stored_checksum, computed_checksum
+        );
+
+        let decode_layer = |stored: &[u8], expected_len: usize| -> Result<Vec<u8>> {
+            let decoded = match mode {
+                CompressionMode::None => stored.to_vec(),
+                CompressionMode::Lz4 => lz4_flex::decompress_size_prepended(stored)
+                    .map_err(|e| anyhow::anyhow!("LZ4 decompress failed: {}", e))?,
+                CompressionMode::Deflate => miniz_oxide::inflate::decompress_to_vec(stored)
+                    .map_err(|e| anyhow::anyhow!("Deflate decompress failed: {:?}", e))?,
+            };
+            ensure!(
+                decoded.len() == expected_len,
+                "Decompressed layer size mismatch: expected {}, got {}",
+                expected_len, decoded.len()
+            );
+            Ok(decoded)
+        };
+
+        let slope_bytes = decode_layer(layer_slices[0], slope_size)?;
+        let flow = decode_layer(layer_slices[1], flow_size)?;
+        let basin_bytes = decode_layer(layer_slices[2], basin_size)?;
+        let accum_bytes = decode_layer(layer_slices[3], accum_size)?;
+        let lake_level_bytes = decode_layer(layer_slices[4], lake_level_size)?;
+
+        let slope: Vec<i16> = slope_bytes
+            .chunks_exact(2)
+            .map(|c| i16::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        let basin: Vec<u32> = basin_bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        let accum: Vec<u32> = accum_bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        let lake_level: Vec<i16> = lake_level_bytes
+            .chunks_exact(2)
+            .map(|c| i16::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            slope,
+            flow,
+            basin,
+            accum,
+            mfd_receivers: vec![[0; 8]; size],
+            mfd_weights: vec![[0.0; 8]; size],
+            lake_level,
+        })
     }
 }